@@ -0,0 +1,929 @@
+use crate::barcode;
+use crate::links::percent_encode;
+use log::{debug, error, info, warn};
+use reqwest::blocking::Response;
+use reqwest::header::RETRY_AFTER;
+use reqwest::StatusCode;
+use serde::{Deserialize, Deserializer, Serialize};
+use std::fmt;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Condvar, Mutex};
+use std::time::{Duration, Instant};
+
+/// Longest we are willing to wait when the server asks us to back off.
+const MAX_RETRY_AFTER_SECS: u64 = 30;
+/// Wait used when a 429 response carries no usable `Retry-After` header.
+const DEFAULT_RETRY_AFTER_SECS: u64 = 5;
+
+/// Maximum number of requests in flight at once, across the whole process.
+pub const MAX_CONCURRENT_REQUESTS: usize = 4;
+
+/// Number of requests currently holding a [`RequestPermit`].
+static REQUESTS_IN_FLIGHT: Mutex<usize> = Mutex::new(0);
+/// Signalled whenever a permit is released.
+static PERMIT_RELEASED: Condvar = Condvar::new();
+
+/// Allows one request to run; released when dropped.
+struct RequestPermit;
+
+impl RequestPermit {
+    /// Waits until fewer than [`MAX_CONCURRENT_REQUESTS`] are in flight, unless cancelled first.
+    fn acquire(hooks: &RequestHooks) -> Result<Self, AppError> {
+        let mut in_flight = REQUESTS_IN_FLIGHT.lock().unwrap_or_else(|e| e.into_inner());
+        while *in_flight >= MAX_CONCURRENT_REQUESTS {
+            if hooks.is_cancelled() {
+                return Err(AppError::Cancelled);
+            }
+            in_flight = PERMIT_RELEASED
+                .wait_timeout(in_flight, Duration::from_millis(100))
+                .unwrap_or_else(|e| e.into_inner())
+                .0;
+        }
+        *in_flight += 1;
+        Ok(RequestPermit)
+    }
+}
+
+impl Drop for RequestPermit {
+    fn drop(&mut self) {
+        let mut in_flight = REQUESTS_IN_FLIGHT.lock().unwrap_or_else(|e| e.into_inner());
+        *in_flight -= 1;
+        PERMIT_RELEASED.notify_one();
+    }
+}
+
+/// Largest page size accepted by the search endpoint.
+pub const MAX_PAGE_SIZE: u32 = 100;
+
+/// Where and how the API is queried.
+#[derive(Clone, Debug)]
+pub struct ApiConfig {
+    /// Connection pool shared by every request made with this configuration (and its clones).
+    pub client: reqwest::blocking::Client,
+    /// Root of the OpenFoodFacts server, without trailing slash.
+    pub base_url: String,
+    /// Language code used for localized fields (`lc` parameter).
+    pub locale: String,
+    /// Maximum duration of a single request, after which it fails with [`AppError::Timeout`].
+    pub timeout: Duration,
+    /// Number of products requested per search page.
+    pub page_size: u32,
+    /// How many times a request is tried before a network error is reported.
+    pub retry_attempts: u32,
+    /// Login sent with requests to [`ApiConfig::base_url`], see [`ApiConfig::credentials_for`].
+    pub credentials: Option<Credentials>,
+    /// Send the login to every server, and not only to the staging ones.
+    pub always_authenticate: bool,
+}
+
+/// A username and password for HTTP basic authentication.
+#[derive(Clone, PartialEq)]
+pub struct Credentials {
+    pub username: String,
+    pub password: String,
+}
+
+impl fmt::Debug for Credentials {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        // Keep the password out of the logs
+        f.debug_struct("Credentials").field("username", &self.username).finish_non_exhaustive()
+    }
+}
+
+impl Default for ApiConfig {
+    fn default() -> Self {
+        Self {
+            client: reqwest::blocking::Client::new(),
+            base_url: Flavor::default().base_url().to_string(),
+            locale: "en".to_string(),
+            timeout: Duration::from_secs(10),
+            page_size: 20,
+            retry_attempts: 3,
+            credentials: None,
+            always_authenticate: false,
+        }
+    }
+}
+
+/// The sibling databases sharing the OpenFoodFacts API.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Flavor {
+    #[default]
+    Food,
+    Beauty,
+    PetFood,
+    Products,
+}
+
+impl Flavor {
+    pub const ALL: [Flavor; 4] = [Flavor::Food, Flavor::Beauty, Flavor::PetFood, Flavor::Products];
+
+    /// Name of the project, as shown to the user.
+    pub fn label(self) -> &'static str {
+        match self {
+            Flavor::Food => "Open Food Facts",
+            Flavor::Beauty => "Open Beauty Facts",
+            Flavor::PetFood => "Open Pet Food Facts",
+            Flavor::Products => "Open Products Facts",
+        }
+    }
+
+    /// Root URL of the project's server.
+    pub fn base_url(self) -> &'static str {
+        match self {
+            Flavor::Food => "https://world.openfoodfacts.org",
+            Flavor::Beauty => "https://world.openbeautyfacts.org",
+            Flavor::PetFood => "https://world.openpetfoodfacts.org",
+            Flavor::Products => "https://world.openproductsfacts.org",
+        }
+    }
+
+    /// Root URL of the project's staging server, protected by basic authentication.
+    pub fn staging_url(self) -> String {
+        self.base_url().replace(".org", ".net")
+    }
+}
+
+impl ApiConfig {
+    /// Whether [`ApiConfig::base_url`] points to a staging (`.net`) server.
+    pub fn is_staging(&self) -> bool {
+        let host = self.base_url.split("://").nth(1).unwrap_or(&self.base_url);
+        let host = host.split(['/', ':']).next().unwrap_or(host);
+        host.ends_with(".net")
+    }
+
+    /// The login to send with a request to `url`: only requests to our own
+    /// server get it, and only on staging unless [`ApiConfig::always_authenticate`] is set.
+    pub fn credentials_for(&self, url: &str) -> Option<&Credentials> {
+        let own_server = url.strip_prefix(self.base_url.as_str()).is_some_and(|rest| rest.is_empty() || rest.starts_with(['/', '?']));
+        let wanted = self.always_authenticate || self.is_staging();
+        self.credentials.as_ref().filter(|_| own_server && wanted)
+    }
+
+    /// Root of the website pages, localized through the `world-<lc>` subdomain.
+    pub fn site_url(&self) -> String {
+        if self.locale == "en" {
+            return self.base_url.clone();
+        }
+        self.base_url
+            .replacen("://world.", &format!("://world-{}.", self.locale), 1)
+    }
+}
+
+/// Page on the website where the data of a product can be corrected.
+pub fn edit_product_url(config: &ApiConfig, code: &str) -> String {
+    format!("{}/cgi/product.pl?type=edit&code={}", config.site_url(), percent_encode(code))
+}
+
+// Structs for API responses
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct Product {
+    pub code: Option<String>,
+    pub product_name: Option<String>,
+    #[serde(default)]
+    pub allergens_tags: Vec<String>,
+    #[serde(default)]
+    pub nutriments: Nutriments,
+    /// Small photo of the front of the package, for lists.
+    pub image_thumb_url: Option<String>,
+}
+
+/// One page of search results.
+#[derive(Deserialize)]
+pub struct SearchResponse {
+    /// Total number of matching products, over all pages.
+    #[serde(default, deserialize_with = "flexible_u64")]
+    pub count: Option<u64>,
+    /// The 1-based number of this page.
+    #[serde(default, deserialize_with = "flexible_u64")]
+    pub page: Option<u64>,
+    #[serde(default, deserialize_with = "flexible_u64")]
+    pub page_size: Option<u64>,
+    /// Number of pages, as reported by the server; see [`SearchResponse::page_count`].
+    #[serde(default, rename = "page_count", deserialize_with = "flexible_u64")]
+    pub reported_page_count: Option<u64>,
+    pub products: Vec<Product>,
+}
+
+impl SearchResponse {
+    /// Number of result pages, at least 1: computed from `count` and `page_size`
+    /// when the server sent both, or else the `page_count` it reported.
+    pub fn page_count(&self) -> u32 {
+        let pages = match (self.count, self.page_size) {
+            (Some(count), Some(page_size)) if page_size > 0 => count.div_ceil(page_size),
+            _ => self.reported_page_count.unwrap_or(1),
+        };
+        pages.clamp(1, u64::from(u32::MAX)) as u32
+    }
+}
+
+#[derive(Deserialize, Serialize, Clone)]
+pub struct ProductDetails {
+    pub code: String,
+    pub product_name: Option<String>,
+    pub ingredients_text: Option<String>,
+    pub brands: Option<String>,
+    /// Net quantity of the package, e.g. `"400 g"`.
+    pub quantity: Option<String>,
+    /// Categories in the language of the product, comma-separated.
+    pub categories: Option<String>,
+    pub serving_size: Option<String>,
+    pub nutrition_grades: Option<String>,
+    pub nova_group: Option<u8>,
+    pub ecoscore_grade: Option<String>,
+    #[serde(default)]
+    pub packagings: Vec<Packaging>,
+    #[serde(default)]
+    pub packaging_tags: Vec<String>,
+    pub last_modified_t: Option<i64>,
+    #[serde(default)]
+    pub nutriments: Nutriments,
+    #[serde(default)]
+    pub categories_tags: Vec<String>,
+    #[serde(default)]
+    pub brands_tags: Vec<String>,
+    #[serde(default)]
+    pub allergens_tags: Vec<String>,
+    #[serde(default)]
+    pub labels_tags: Vec<String>,
+    /// Full-size photo of the front of the package.
+    pub image_front_url: Option<String>,
+    /// Small version of `image_front_url`.
+    pub image_thumb_url: Option<String>,
+    /// Number of ingredients recognised in `ingredients_text`.
+    #[serde(default, deserialize_with = "flexible_u64")]
+    pub ingredients_n: Option<u64>,
+    /// Completion state of the product page, e.g. `"en:ingredients-to-be-completed"`.
+    #[serde(default)]
+    pub states_tags: Vec<String>,
+    // other fields...
+}
+
+/// Nutrition facts per 100g (or 100ml), missing when the product does not declare them.
+#[derive(Deserialize, Serialize, Debug, Clone, Default)]
+pub struct Nutriments {
+    #[serde(rename = "energy-kcal_100g", default, deserialize_with = "flexible_f64")]
+    pub energy_kcal_100g: Option<f64>,
+    #[serde(default, deserialize_with = "flexible_f64")]
+    pub fat_100g: Option<f64>,
+    #[serde(rename = "saturated-fat_100g", default, deserialize_with = "flexible_f64")]
+    pub saturated_fat_100g: Option<f64>,
+    #[serde(default, deserialize_with = "flexible_f64")]
+    pub carbohydrates_100g: Option<f64>,
+    #[serde(default, deserialize_with = "flexible_f64")]
+    pub sugars_100g: Option<f64>,
+    #[serde(default, deserialize_with = "flexible_f64")]
+    pub fiber_100g: Option<f64>,
+    #[serde(default, deserialize_with = "flexible_f64")]
+    pub proteins_100g: Option<f64>,
+    #[serde(default, deserialize_with = "flexible_f64")]
+    pub salt_100g: Option<f64>,
+}
+
+/// Accepts a number, a numeric string (`"12.5"`, `"12,5"`) or null, so that one
+/// badly typed user-contributed value does not fail the whole product.
+fn flexible_f64<'de, D>(deserializer: D) -> Result<Option<f64>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let value = serde_json::Value::deserialize(deserializer)?;
+    Ok(match value {
+        serde_json::Value::Number(number) => number.as_f64(),
+        serde_json::Value::String(text) => {
+            let parsed = text.trim().replace(',', ".").parse::<f64>().ok();
+            match parsed {
+                Some(number) => warn!("Coerced string {:?} to number {}", text, number),
+                None => warn!("Ignoring non-numeric value {:?}", text),
+            }
+            parsed
+        }
+        serde_json::Value::Null => None,
+        other => {
+            warn!("Ignoring unexpected value {} where a number was expected", other);
+            None
+        }
+    })
+}
+
+/// Like [`flexible_f64`], for counts that are sometimes sent as strings.
+fn flexible_u64<'de, D>(deserializer: D) -> Result<Option<u64>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    Ok(flexible_f64(deserializer)?.filter(|number| *number >= 0.0).map(|number| number as u64))
+}
+
+/// One component of a product packaging (e.g. the bottle, or its cap).
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct Packaging {
+    pub shape: Option<String>,
+    pub material: Option<String>,
+    pub recycling: Option<String>,
+}
+
+/// Returns the watchlist entries (e.g. `"nuts"`) matching one of `allergens_tags` (e.g. `"en:nuts"`).
+pub fn watched_allergens<'a>(allergens_tags: &[String], watchlist: &'a [String]) -> Vec<&'a str> {
+    watchlist
+        .iter()
+        .filter(|watched| {
+            let watched = watched.trim().replace(' ', "-");
+            allergens_tags.iter().any(|tag| {
+                let name = tag.split_once(':').map_or(tag.as_str(), |(_, name)| name);
+                name.eq_ignore_ascii_case(&watched)
+            })
+        })
+        .map(String::as_str)
+        .collect()
+}
+
+/// Turns a taxonomy tag such as `"en:pet-1-polyethylene-terephthalate"` into readable text.
+pub fn tag_label(tag: &str) -> String {
+    let name = tag.split_once(':').map_or(tag, |(_, name)| name).replace('-', " ");
+    let mut chars = name.chars();
+    match chars.next() {
+        Some(first) => first.to_uppercase().chain(chars).collect(),
+        None => String::new(),
+    }
+}
+
+/// Turns an `ingredients_text` into plain text, removing the underscores that
+/// mark allergens (`"_milk_ chocolate"` becomes `"milk chocolate"`) and
+/// collapsing runs of whitespace.
+///
+/// Underscores inside a word are kept, since they are not markup.
+pub fn clean_ingredients(raw: &str) -> String {
+    let chars: Vec<char> = raw.chars().collect();
+    let is_word = |index: Option<usize>| index.and_then(|index| chars.get(index)).is_some_and(|c| c.is_alphanumeric());
+    let text: String = chars
+        .iter()
+        .enumerate()
+        .filter(|&(index, &c)| c != '_' || (is_word(index.checked_sub(1)) && is_word(Some(index + 1))))
+        .map(|(_, &c)| c)
+        .collect();
+    text.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+impl ProductDetails {
+    /// The short summary used in lists (search results, history...).
+    pub fn summary(&self) -> Product {
+        Product {
+            code: Some(self.code.clone()),
+            product_name: self.product_name.clone(),
+            allergens_tags: self.allergens_tags.clone(),
+            nutriments: self.nutriments.clone(),
+            image_thumb_url: self.image_thumb_url.clone(),
+        }
+    }
+
+    /// A search for comparable products: its most specific category, or else its first brand.
+    pub fn similar_query(&self) -> Option<SearchQuery> {
+        if let Some(category) = self.categories_tags.last() {
+            return Some(SearchQuery::Category(category.clone()));
+        }
+        if let Some(brand) = self.brands_tags.first() {
+            return Some(SearchQuery::Brand(brand.clone()));
+        }
+        self.brands
+            .as_deref()
+            .and_then(|brands| brands.split(',').map(str::trim).find(|brand| !brand.is_empty()))
+            .map(|brand| SearchQuery::Brand(brand.to_lowercase().replace(' ', "-")))
+    }
+
+    /// The Nutri-Score letter (`'a'` to `'e'`), if known.
+    pub fn nutriscore(&self) -> Option<char> {
+        self.nutrition_grades.as_deref().and_then(grade_letter)
+    }
+
+    /// The NOVA processing group (1 to 4), if known.
+    pub fn nova(&self) -> Option<u8> {
+        self.nova_group.filter(|group| (1..=4).contains(group))
+    }
+
+    /// The Eco-Score letter (`'a'` to `'e'`), if known.
+    pub fn ecoscore(&self) -> Option<char> {
+        self.ecoscore_grade.as_deref().and_then(grade_letter)
+    }
+
+    /// The categories as listed on the product, or else the labels of its category tags.
+    pub fn categories_label(&self) -> Option<String> {
+        let listed = self.categories.as_deref().map(str::trim).filter(|categories| !categories.is_empty());
+        match listed {
+            Some(categories) => Some(categories.to_string()),
+            None if !self.categories_tags.is_empty() => {
+                Some(self.categories_tags.iter().map(|tag| tag_label(tag)).collect::<Vec<_>>().join(", "))
+            }
+            None => None,
+        }
+    }
+
+    /// Whether the ingredients have not been fully analysed, so the allergen
+    /// and ingredient analysis may be missing things.
+    pub fn ingredients_incomplete(&self) -> bool {
+        let has_text = self.ingredients_text.as_deref().is_some_and(|text| !text.trim().is_empty());
+        self.states_tags.iter().any(|tag| tag == "en:ingredients-to-be-completed")
+            || (has_text && self.ingredients_n == Some(0))
+    }
+}
+
+/// Reads an `a`..`e` grade, ignoring values such as `"unknown"` or `"not-applicable"`.
+fn grade_letter(grade: &str) -> Option<char> {
+    let mut chars = grade.trim().chars();
+    match (chars.next().map(|c| c.to_ascii_lowercase()), chars.next()) {
+        (Some(letter @ 'a'..='e'), None) => Some(letter),
+        _ => None,
+    }
+}
+
+#[derive(Deserialize)]
+pub struct ProductDetailsResponse {
+    /// `1` when the product exists, `0` otherwise.
+    pub status: Option<i64>,
+    pub product: Option<ProductDetails>,
+}
+
+/// Errors returned by the API functions.
+#[derive(Debug, Clone, PartialEq)]
+pub enum AppError {
+    /// The request could not be completed (rate limiting, interrupted transfer...).
+    Network(String),
+    /// The server could not be reached at all (no route, DNS failure, connection refused).
+    Offline,
+    /// The server did not answer within [`ApiConfig::timeout`].
+    Timeout,
+    /// The server answered with a non-success HTTP status.
+    Http(u16),
+    /// The server answered but the body was not what we expected.
+    Parse(String),
+    /// No product exists with the requested code.
+    NotFound,
+    /// The request was abandoned through [`RequestHooks::cancel`].
+    Cancelled,
+}
+
+impl fmt::Display for AppError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AppError::Network(msg) => write!(f, "Network error: {}", msg),
+            AppError::Offline => write!(f, "You appear to be offline"),
+            AppError::Timeout => write!(f, "The server took too long to answer"),
+            AppError::Http(status) => write!(f, "Server returned HTTP {}", status),
+            AppError::Parse(msg) => write!(f, "Failed to parse response: {}", msg),
+            AppError::NotFound => write!(f, "Product not found"),
+            AppError::Cancelled => write!(f, "Request cancelled"),
+        }
+    }
+}
+
+impl std::error::Error for AppError {}
+
+impl From<reqwest::Error> for AppError {
+    /// Classifies a transport failure: unreachable hosts are [`AppError::Offline`],
+    /// undecodable bodies [`AppError::Parse`], status errors [`AppError::Http`]
+    /// and anything else a [`AppError::Network`] error.
+    fn from(e: reqwest::Error) -> Self {
+        if e.is_connect() {
+            AppError::Offline
+        } else if e.is_timeout() {
+            AppError::Timeout
+        } else if e.is_decode() {
+            AppError::Parse(e.to_string())
+        } else if let Some(status) = e.status() {
+            AppError::Http(status.as_u16())
+        } else {
+            AppError::Network(e.to_string())
+        }
+    }
+}
+
+impl From<serde_json::Error> for AppError {
+    fn from(e: serde_json::Error) -> Self {
+        AppError::Parse(e.to_string())
+    }
+}
+
+/// Lets the caller observe and abort a request while it runs.
+#[derive(Clone, Default)]
+pub struct RequestHooks {
+    /// Set to `true` to abandon the request at the next opportunity.
+    pub cancel: Arc<AtomicBool>,
+    /// Called with the wait in seconds when the server rate-limits us and we retry.
+    pub on_rate_limit: Option<Arc<dyn Fn(u64) + Send + Sync>>,
+    /// Called once every download is over, successful or not.
+    pub on_request: Option<Arc<dyn Fn(RequestRecord) + Send + Sync>>,
+    /// Called instead of waiting before a retry, e.g. so that tests need not sleep.
+    pub wait: Option<Arc<dyn Fn(Duration) + Send + Sync>>,
+}
+
+/// What happened to one download, for troubleshooting.
+#[derive(Clone, Debug)]
+pub struct RequestRecord {
+    pub url: String,
+    /// The HTTP status, unless no response was received.
+    pub status: Option<u16>,
+    pub duration: Duration,
+    /// Size of the body received, if the download completed.
+    pub bytes: Option<usize>,
+    pub error: Option<String>,
+}
+
+impl RequestHooks {
+    /// Reports the `outcome` (status and body size) of the download of `url` to [`RequestHooks::on_request`].
+    fn record(&self, url: &str, started: Instant, outcome: Result<(u16, usize), &AppError>) {
+        let Some(on_request) = &self.on_request else {
+            return;
+        };
+        let (status, bytes, error) = match outcome {
+            Ok((status, bytes)) => (Some(status), Some(bytes), None),
+            Err(AppError::Http(status)) => (Some(*status), None, None),
+            Err(err) => (None, None, Some(err.to_string())),
+        };
+        on_request(RequestRecord {
+            url: url.to_string(),
+            status,
+            duration: started.elapsed(),
+            bytes,
+            error,
+        });
+    }
+
+    fn is_cancelled(&self) -> bool {
+        self.cancel.load(Ordering::Relaxed)
+    }
+
+    /// Sleeps for `duration`, waking up regularly to honor cancellation.
+    fn sleep(&self, duration: Duration) -> Result<(), AppError> {
+        if let Some(wait) = &self.wait {
+            wait(duration);
+            return if self.is_cancelled() { Err(AppError::Cancelled) } else { Ok(()) };
+        }
+        let deadline = Instant::now() + duration;
+        loop {
+            if self.is_cancelled() {
+                return Err(AppError::Cancelled);
+            }
+            let now = Instant::now();
+            if now >= deadline {
+                return Ok(());
+            }
+            std::thread::sleep((deadline - now).min(Duration::from_millis(100)));
+        }
+    }
+}
+
+/// Checks whether the server can be reached, whatever it answers.
+pub fn is_reachable(config: &ApiConfig) -> bool {
+    let Ok(_permit) = RequestPermit::acquire(&RequestHooks::default()) else {
+        return false;
+    };
+    config.client.head(&config.base_url).timeout(config.timeout).send().is_ok()
+}
+
+/// Reads the `Retry-After` header as a number of seconds, capped to a sane maximum.
+fn retry_after_secs(response: &Response) -> u64 {
+    response
+        .headers()
+        .get(RETRY_AFTER)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.trim().parse::<u64>().ok())
+        .unwrap_or(DEFAULT_RETRY_AFTER_SECS)
+        .min(MAX_RETRY_AFTER_SECS)
+}
+
+/// Downloads the body of `url` as text, holding a [`RequestPermit`] for the whole transfer.
+fn get_text(config: &ApiConfig, url: &str, hooks: &RequestHooks) -> Result<String, AppError> {
+    let started = Instant::now();
+    let result = send(config, url, hooks).and_then(|(_permit, response)| {
+        let status = response.status().as_u16();
+        let body = response.text().inspect_err(|e| error!("Failed to read response: {}", e))?;
+        Ok((status, body))
+    });
+    hooks.record(url, started, result.as_ref().map(|(status, body)| (*status, body.len())));
+    let (_, body) = result?;
+    // An empty body would otherwise surface as a confusing EOF parse error
+    if body.trim().is_empty() {
+        warn!("Empty response from {}", url);
+        return Err(AppError::Network("Empty response from server".to_string()));
+    }
+    Ok(body)
+}
+
+/// Delay before the first retry of [`with_retry`], doubled for every further one.
+const RETRY_BASE_DELAY: Duration = Duration::from_millis(200);
+
+/// Message of the [`AppError::Network`] returned by [`send`] when the server
+/// still rate-limits us after waiting, which retrying sooner cannot fix.
+const STILL_RATE_LIMITED: &str = "Rate limited by the server";
+
+/// Whether `err` may go away by trying again a moment later.
+fn is_transient(err: &AppError) -> bool {
+    match err {
+        AppError::Network(msg) => msg != STILL_RATE_LIMITED,
+        AppError::Timeout => true,
+        _ => false,
+    }
+}
+
+/// Calls `f` up to `attempts` times while it fails with a transient
+/// [`AppError::Network`] or [`AppError::Timeout`] error, waiting exponentially
+/// longer (with some jitter, so clients do not retry in lockstep) in between.
+///
+/// Any other error, or the last transient one, is returned as is; cancelling
+/// `hooks` during a wait returns [`AppError::Cancelled`].
+pub fn with_retry<T>(attempts: u32, hooks: &RequestHooks, f: impl Fn() -> Result<T, AppError>) -> Result<T, AppError> {
+    let attempts = attempts.max(1);
+    let mut attempt = 1;
+    loop {
+        match f() {
+            Err(err) if attempt < attempts && is_transient(&err) => {
+                let backoff = RETRY_BASE_DELAY * 2u32.pow(attempt - 1);
+                let jitter = backoff.mul_f64(jitter_fraction() / 2.0);
+                warn!("{} (attempt {} of {}), retrying in {:?}", err, attempt, attempts, backoff + jitter);
+                hooks.sleep(backoff + jitter)?;
+                attempt += 1;
+            }
+            result => return result,
+        }
+    }
+}
+
+/// A pseudo-random number in `0.0..1.0`, good enough to spread out retries.
+fn jitter_fraction() -> f64 {
+    let nanos = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map_or(0, |elapsed| elapsed.subsec_nanos());
+    f64::from(nanos % 1000) / 1000.0
+}
+
+/// Performs a GET request, retrying once if the server answers 429 Too Many Requests.
+///
+/// Returns the response with the [`RequestPermit`] it was sent under, to be held
+/// while its body is read; the permit is given back while waiting to retry.
+fn send(config: &ApiConfig, url: &str, hooks: &RequestHooks) -> Result<(RequestPermit, Response), AppError> {
+    let send = || -> Result<(RequestPermit, Response), AppError> {
+        let permit = RequestPermit::acquire(hooks)?;
+        let mut request = config.client.get(url).timeout(config.timeout);
+        if let Some(credentials) = config.credentials_for(url) {
+            request = request.basic_auth(&credentials.username, Some(&credentials.password));
+        }
+        let response = request.send().inspect_err(|e| error!("Failed to get response: {}", e))?;
+        debug!("Received response with status: {}", response.status());
+        Ok((permit, response))
+    };
+
+    let (permit, response) = send()?;
+    if response.status() != StatusCode::TOO_MANY_REQUESTS {
+        return Ok((permit, ensure_success(response)?));
+    }
+
+    let wait = retry_after_secs(&response);
+    warn!("Rate limited, retrying in {}s", wait);
+    if let Some(on_rate_limit) = &hooks.on_rate_limit {
+        on_rate_limit(wait);
+    }
+    // Let other requests run in the meantime
+    drop((permit, response));
+    hooks.sleep(Duration::from_secs(wait))?;
+
+    let (permit, response) = send()?;
+    if response.status() == StatusCode::TOO_MANY_REQUESTS {
+        error!("Still rate limited after retrying");
+        return Err(AppError::Network(STILL_RATE_LIMITED.to_string()));
+    }
+    Ok((permit, ensure_success(response)?))
+}
+
+/// Turns a non-success HTTP status into an [`AppError::Http`].
+fn ensure_success(response: Response) -> Result<Response, AppError> {
+    let status = response.status();
+    if status.is_success() {
+        Ok(response)
+    } else {
+        error!("Server returned HTTP {}", status);
+        Err(AppError::Http(status.as_u16()))
+    }
+}
+
+/// What a search looks for: free keywords, or every product carrying a tag.
+#[derive(Clone, Debug, PartialEq)]
+pub enum SearchQuery {
+    Terms(String),
+    /// A category tag such as `"en:hazelnut-spreads"`.
+    Category(String),
+    /// A brand tag such as `"ferrero"`.
+    Brand(String),
+}
+
+impl SearchQuery {
+    /// Short description for the user, e.g. `'milk'` or `category 'Hazelnut spreads'`.
+    pub fn describe(&self) -> String {
+        match self {
+            SearchQuery::Terms(terms) => format!("'{}'", terms),
+            SearchQuery::Category(tag) => format!("category '{}'", tag_label(tag)),
+            SearchQuery::Brand(tag) => format!("brand '{}'", tag_label(tag)),
+        }
+    }
+
+    /// The `search.pl` parameters selecting the products, percent-encoded.
+    fn query_string(&self) -> String {
+        match self {
+            SearchQuery::Terms(terms) => format!("search_terms={}", percent_encode(terms)),
+            SearchQuery::Category(tag) => format!("tagtype_0=categories&tag_contains_0=contains&tag_0={}", percent_encode(tag)),
+            SearchQuery::Brand(tag) => format!("tagtype_0=brands&tag_contains_0=contains&tag_0={}", percent_encode(tag)),
+        }
+    }
+}
+
+/// Orders the server can sort all the results of a search by.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SortBy {
+    /// Most scanned products first.
+    Popularity,
+    /// Best Nutri-Score first.
+    NutriScore,
+    /// Most recently added first.
+    Created,
+    /// Most recently edited first.
+    LastModified,
+}
+
+impl SortBy {
+    pub const ALL: [SortBy; 4] = [SortBy::Popularity, SortBy::NutriScore, SortBy::Created, SortBy::LastModified];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            SortBy::Popularity => "Popularity",
+            SortBy::NutriScore => "Nutri-Score",
+            SortBy::Created => "Newest",
+            SortBy::LastModified => "Last modified",
+        }
+    }
+
+    /// Value of the `sort_by` parameter.
+    pub fn param(self) -> &'static str {
+        match self {
+            SortBy::Popularity => "unique_scans_n",
+            SortBy::NutriScore => "nutriscore_score",
+            SortBy::Created => "created_t",
+            SortBy::LastModified => "last_modified_t",
+        }
+    }
+
+    /// Reads a value of the `sort_by` parameter written by [`SortBy::param`].
+    pub fn from_param(param: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|sort| sort.param() == param)
+    }
+}
+
+/// Categories offered for browsing, as `(tag, label)` pairs.
+pub const BROWSE_CATEGORIES: [(&str, &str); 8] = [
+    ("en:snacks", "Snacks"),
+    ("en:beverages", "Beverages"),
+    ("en:dairies", "Dairy"),
+    ("en:breakfast-cereals", "Cereals"),
+    ("en:breads", "Breads"),
+    ("en:cheeses", "Cheeses"),
+    ("en:chocolates", "Chocolates"),
+    ("en:frozen-foods", "Frozen foods"),
+];
+
+/// Fields of [`Product`], so that searches do not download whole product pages.
+const SEARCH_FIELDS: &str = "code,product_name,allergens_tags,nutriments,image_thumb_url";
+
+/// Fetches the given 1-based `page` of products matching `query` from the `search.pl` endpoint,
+/// in the server's relevance order unless `sort` is given.
+///
+/// Categories are sorted by popularity by default, since their products have no relevance order.
+pub fn search_products(
+    config: &ApiConfig,
+    query: &SearchQuery,
+    page: u32,
+    sort: Option<SortBy>,
+    hooks: &RequestHooks,
+) -> Result<SearchResponse, AppError> {
+    let sort = sort.or(matches!(query, SearchQuery::Category(_)).then_some(SortBy::Popularity));
+    let url = format!(
+        "{}/cgi/search.pl?{}{}&search_simple=1&json=1&fields={}&page={}&page_size={}&lc={}",
+        config.base_url,
+        query.query_string(),
+        sort.map_or_else(String::new, |sort| format!("&sort_by={}", sort.param())),
+        SEARCH_FIELDS,
+        page.max(1),
+        config.page_size.clamp(1, MAX_PAGE_SIZE),
+        config.locale
+    );
+    debug!("Requesting search results from: {}", url);
+    let body = with_retry(config.retry_attempts, hooks, || get_text(config, &url, hooks))?;
+    let search_response = serde_json::from_str::<SearchResponse>(&body).inspect_err(|e| error!("Failed to parse response: {}", e))?;
+    info!("Successfully parsed search results.");
+    Ok(search_response)
+}
+
+/// Fetches the full details of a single product by its barcode.
+pub fn fetch_product(config: &ApiConfig, code: &str, hooks: &RequestHooks) -> Result<ProductDetails, AppError> {
+    let url = format!(
+        "{}/api/v0/product/{}.json?lc={}",
+        config.base_url,
+        percent_encode(code),
+        config.locale
+    );
+    debug!("Requesting product details from: {}", url);
+    let body = with_retry(config.retry_attempts, hooks, || get_text(config, &url, hooks))?;
+    match serde_json::from_str::<ProductDetailsResponse>(&body).inspect_err(|e| error!("Failed to parse details: {}", e))? {
+        ProductDetailsResponse { status: Some(0), .. } | ProductDetailsResponse { product: None, .. } => {
+            info!("Product {} not found.", code);
+            Err(AppError::NotFound)
+        }
+        ProductDetailsResponse { product: Some(product), .. } => {
+            info!("Successfully parsed product details.");
+            Ok(product)
+        }
+    }
+}
+
+/// Size of the chunks image downloads are read in, between two progress reports.
+const DOWNLOAD_CHUNK_SIZE: usize = 16 * 1024;
+
+/// Downloads an image, reporting the fraction received so far to `on_progress`
+/// after every chunk, or `None` when the server did not send a `Content-Length`.
+pub fn download_image(
+    config: &ApiConfig,
+    url: &str,
+    hooks: &RequestHooks,
+    mut on_progress: impl FnMut(Option<f32>),
+) -> Result<Vec<u8>, AppError> {
+    debug!("Downloading image from: {}", url);
+    let started = Instant::now();
+    let result = send(config, url, hooks).and_then(|(_permit, response)| {
+        let status = response.status().as_u16();
+        read_chunks(response, hooks, &mut on_progress).map(|bytes| (status, bytes))
+    });
+    hooks.record(url, started, result.as_ref().map(|(status, bytes)| (*status, bytes.len())));
+    result.map(|(_, bytes)| bytes)
+}
+
+/// Reads the body of an image download, see [`download_image`].
+fn read_chunks(mut response: Response, hooks: &RequestHooks, on_progress: &mut impl FnMut(Option<f32>)) -> Result<Vec<u8>, AppError> {
+    let total = response.content_length().filter(|&total| total > 0);
+    let mut bytes = Vec::with_capacity(total.map_or(0, |total| total as usize));
+    let mut chunk = vec![0; DOWNLOAD_CHUNK_SIZE];
+    loop {
+        if hooks.is_cancelled() {
+            return Err(AppError::Cancelled);
+        }
+        let read = std::io::Read::read(&mut response, &mut chunk).map_err(|e| {
+            error!("Failed to read image: {}", e);
+            AppError::Network(e.to_string())
+        })?;
+        if read == 0 {
+            break;
+        }
+        bytes.extend_from_slice(&chunk[..read]);
+        on_progress(total.map(|total| (bytes.len() as f64 / total as f64).min(1.0) as f32));
+    }
+    Ok(bytes)
+}
+
+/// Fetches a product like [`fetch_product`], retrying the other spellings of
+/// its barcode from [`barcode::code_variants`] while it is not found.
+///
+/// The `code` of the returned product tells which spelling matched.
+pub fn lookup_product(config: &ApiConfig, code: &str, hooks: &RequestHooks) -> Result<ProductDetails, AppError> {
+    let mut result = Err(AppError::NotFound);
+    for variant in barcode::code_variants(code) {
+        result = fetch_product(config, &variant, hooks);
+        match &mut result {
+            Err(AppError::NotFound) => debug!("No product with code {}, trying the next variant", variant),
+            Ok(product) => {
+                if product.code.is_empty() {
+                    product.code = variant;
+                }
+                break;
+            }
+            Err(_) => break,
+        }
+    }
+    result
+}
+
+/// The requests the viewer makes, so that tests can answer them without a server.
+pub trait OpenFoodFactsClient: Send + Sync {
+    /// See [`search_products`].
+    fn search(&self, config: &ApiConfig, query: &SearchQuery, page: u32, sort: Option<SortBy>, hooks: &RequestHooks) -> Result<SearchResponse, AppError>;
+
+    /// See [`lookup_product`].
+    fn product(&self, config: &ApiConfig, code: &str, hooks: &RequestHooks) -> Result<ProductDetails, AppError>;
+}
+
+/// Queries the server described by the [`ApiConfig`] over HTTP.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct ReqwestClient;
+
+impl OpenFoodFactsClient for ReqwestClient {
+    fn search(&self, config: &ApiConfig, query: &SearchQuery, page: u32, sort: Option<SortBy>, hooks: &RequestHooks) -> Result<SearchResponse, AppError> {
+        search_products(config, query, page, sort, hooks)
+    }
+
+    fn product(&self, config: &ApiConfig, code: &str, hooks: &RequestHooks) -> Result<ProductDetails, AppError> {
+        lookup_product(config, code, hooks)
+    }
+}
@@ -0,0 +1,100 @@
+//! Barcode normalization, so that codes typed or stored with a different
+//! number of leading zeros still find their product.
+
+/// Number of digits of an EAN-13, the length OpenFoodFacts stores most codes with.
+const EAN13_LEN: usize = 13;
+
+/// The codes to try, in order, when looking up `code`.
+///
+/// The code as entered comes first, followed by the same code with one
+/// leading zero less or more (UPC-A ↔ EAN-13) and zero-padded to an EAN-13.
+/// Codes that are not only digits are returned unchanged.
+pub fn code_variants(code: &str) -> Vec<String> {
+    let code = code.trim();
+    let mut variants = vec![code.to_string()];
+    if code.is_empty() || !code.chars().all(|c| c.is_ascii_digit()) {
+        return variants;
+    }
+    let candidates = [
+        code.strip_prefix('0').filter(|rest| !rest.is_empty()).map(str::to_string),
+        (code.len() < EAN13_LEN).then(|| format!("0{}", code)),
+        (code.len() < EAN13_LEN).then(|| format!("{:0>width$}", code, width = EAN13_LEN)),
+    ];
+    for candidate in candidates.into_iter().flatten() {
+        if !variants.contains(&candidate) {
+            variants.push(candidate);
+        }
+    }
+    variants
+}
+
+/// Lengths of the codes [`extract_code`] looks for: EAN-8, UPC-A and EAN-13.
+const CODE_LENGTHS: [usize; 3] = [8, 12, 13];
+/// Longest of [`CODE_LENGTHS`].
+const MAX_CODE_LENGTH: usize = CODE_LENGTHS[2];
+
+/// Whether the last digit of `code` is the GS1 check digit of the others.
+pub fn has_valid_check_digit(code: &str) -> bool {
+    let Some(digits) = code.chars().map(|c| c.to_digit(10)).collect::<Option<Vec<_>>>() else {
+        return false;
+    };
+    let Some((&check, payload)) = digits.split_last() else {
+        return false;
+    };
+    // Weights alternate 3, 1, 3... starting next to the check digit
+    let sum: u32 = payload.iter().rev().zip([3, 1].into_iter().cycle()).map(|(digit, weight)| digit * weight).sum();
+    (10 - sum % 10) % 10 == check
+}
+
+/// Finds the first EAN-8, UPC-A or EAN-13 code in pasted text such as
+/// `"EAN: 3-017620-422003"` or a product page URL.
+///
+/// Groups of digits separated by a single space or dash are joined, and a code
+/// only counts if its check digit is right.
+pub fn extract_code(text: &str) -> Option<String> {
+    for run in digit_runs(text) {
+        for start in 0..run.len() {
+            // Keep the longest span, so that a code is not cut into a shorter valid one;
+            // spans past the longest code length can only get longer
+            let mut longest = None;
+            let mut len = 0;
+            for end in start + 1..=run.len() {
+                len += run[end - 1].len();
+                if len > MAX_CODE_LENGTH {
+                    break;
+                }
+                if CODE_LENGTHS.contains(&len) {
+                    let candidate = run[start..end].concat();
+                    if has_valid_check_digit(&candidate) {
+                        longest = Some(candidate);
+                    }
+                }
+            }
+            if longest.is_some() {
+                return longest;
+            }
+        }
+    }
+    None
+}
+
+/// The groups of digits of `text`, gathered into runs of groups only a single space or dash apart.
+fn digit_runs(text: &str) -> Vec<Vec<&str>> {
+    let mut runs: Vec<Vec<&str>> = Vec::new();
+    let mut previous_end = None;
+    let mut rest = text;
+    let mut offset = 0;
+    while let Some(start) = rest.find(|c: char| c.is_ascii_digit()) {
+        let len = rest[start..].find(|c: char| !c.is_ascii_digit()).unwrap_or(rest.len() - start);
+        let (group_start, group_end) = (offset + start, offset + start + len);
+        let joined = previous_end.is_some_and(|end| matches!(&text[end..group_start], " " | "-"));
+        match runs.last_mut() {
+            Some(run) if joined => run.push(&text[group_start..group_end]),
+            _ => runs.push(vec![&text[group_start..group_end]]),
+        }
+        previous_end = Some(group_end);
+        rest = &text[group_end..];
+        offset = group_end;
+    }
+    runs
+}
@@ -0,0 +1,96 @@
+//! Product details kept between lookups, and across sessions on disk.
+use crate::api::ProductDetails;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::Path;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// Default lifetime of a cached product.
+pub const DEFAULT_TTL: Duration = Duration::from_secs(24 * 60 * 60);
+/// Maximum number of products kept; the oldest are evicted first.
+pub const MAX_ENTRIES: usize = 200;
+
+/// Current time in seconds since the Unix epoch, the unit of the cache timestamps.
+pub fn now() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |elapsed| elapsed.as_secs())
+}
+
+#[derive(Serialize, Deserialize)]
+struct CachedDetails {
+    /// When the details were fetched, in seconds since the Unix epoch.
+    fetched_at: u64,
+    details: ProductDetails,
+}
+
+/// Product details by source and barcode, each stamped with its fetch time.
+///
+/// The source names the server and language the details were fetched from, so
+/// that switching either does not show details of the other.
+#[derive(Serialize, Deserialize, Default)]
+pub struct DetailsCache {
+    entries: HashMap<String, CachedDetails>,
+}
+
+fn key(source: &str, code: &str) -> String {
+    format!("{} {}", source, code)
+}
+
+impl DetailsCache {
+    /// Reads a cache saved by [`DetailsCache::save`], starting empty if the file is missing or corrupt.
+    pub fn load(path: &Path) -> Self {
+        std::fs::read_to_string(path)
+            .ok()
+            .and_then(|json| serde_json::from_str(&json).ok())
+            .unwrap_or_default()
+    }
+
+    /// Writes the entries still valid at `now` to `path`.
+    pub fn save(&mut self, path: &Path, ttl: Duration, now: u64) -> std::io::Result<()> {
+        self.prune(ttl, now);
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        let json = serde_json::to_string(self).map_err(std::io::Error::other)?;
+        std::fs::write(path, json)
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// The details of `code` from `source`, unless missing or older than `ttl` at `now`.
+    pub fn get(&self, source: &str, code: &str, ttl: Duration, now: u64) -> Option<&ProductDetails> {
+        self.entries
+            .get(&key(source, code))
+            .filter(|entry| !is_expired(entry, ttl, now))
+            .map(|entry| &entry.details)
+    }
+
+    /// Stores `details` as fetched from `source` at `now`, evicting the oldest entries beyond [`MAX_ENTRIES`].
+    pub fn insert(&mut self, source: &str, details: ProductDetails, now: u64) {
+        self.entries.insert(key(source, &details.code), CachedDetails { fetched_at: now, details });
+        while self.entries.len() > MAX_ENTRIES {
+            let oldest = self
+                .entries
+                .iter()
+                .min_by_key(|(_, entry)| entry.fetched_at)
+                .map(|(key, _)| key.clone());
+            if let Some(key) = oldest {
+                self.entries.remove(&key);
+            }
+        }
+    }
+
+    /// Drops the entries older than `ttl` at `now`.
+    pub fn prune(&mut self, ttl: Duration, now: u64) {
+        self.entries.retain(|_, entry| !is_expired(entry, ttl, now));
+    }
+}
+
+fn is_expired(entry: &CachedDetails, ttl: Duration, now: u64) -> bool {
+    now.saturating_sub(entry.fetched_at) > ttl.as_secs()
+}
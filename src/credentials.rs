@@ -0,0 +1,41 @@
+//! Login to the OpenFoodFacts servers, with the password kept in the OS keyring.
+use crate::api::Credentials;
+use keyring::Entry;
+
+/// Keyring service the passwords are stored under.
+const KEYRING_SERVICE: &str = "offviewer";
+
+fn entry(username: &str) -> keyring::Result<Entry> {
+    Entry::new(KEYRING_SERVICE, username)
+}
+
+/// Reads the password saved for `username`, if any.
+pub fn load(username: &str) -> Option<Credentials> {
+    if username.is_empty() {
+        return None;
+    }
+    match entry(username).and_then(|entry| entry.get_password()) {
+        Ok(password) => Some(Credentials {
+            username: username.to_string(),
+            password,
+        }),
+        Err(keyring::Error::NoEntry) => None,
+        Err(e) => {
+            log::warn!("Failed to read the keyring: {}", e);
+            None
+        }
+    }
+}
+
+/// Saves the password of `credentials` in the keyring.
+pub fn store(credentials: &Credentials) -> keyring::Result<()> {
+    entry(&credentials.username)?.set_password(&credentials.password)
+}
+
+/// Removes the password saved for `username`; forgetting an unknown user is not an error.
+pub fn forget(username: &str) -> keyring::Result<()> {
+    match entry(username)?.delete_credential() {
+        Err(keyring::Error::NoEntry) => Ok(()),
+        result => result,
+    }
+}
@@ -0,0 +1,38 @@
+//! Saving search results for spreadsheets and scripts.
+use crate::api::Product;
+
+/// Columns of [`results_csv`], in order.
+const CSV_HEADER: &str = "code,product_name,energy_kcal_100g,sugars_100g,salt_100g,allergens";
+
+/// The products as CSV, one row per product, leaving unknown values empty.
+pub fn results_csv(products: &[Product]) -> String {
+    let mut csv = format!("{}\n", CSV_HEADER);
+    for product in products {
+        let number = |value: Option<f64>| value.map_or_else(String::new, |value| value.to_string());
+        let row = [
+            csv_field(product.code.as_deref().unwrap_or_default()),
+            csv_field(product.product_name.as_deref().unwrap_or_default()),
+            number(product.nutriments.energy_kcal_100g),
+            number(product.nutriments.sugars_100g),
+            number(product.nutriments.salt_100g),
+            csv_field(&product.allergens_tags.join(" ")),
+        ];
+        csv.push_str(&row.join(","));
+        csv.push('\n');
+    }
+    csv
+}
+
+/// The products as a pretty-printed JSON array, in the shape the search API returns them.
+pub fn results_json(products: &[Product]) -> String {
+    serde_json::to_string_pretty(products).unwrap_or_else(|_| "[]".to_string())
+}
+
+/// Quotes `value` when it contains a separator, a quote or a line break.
+fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
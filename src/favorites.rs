@@ -0,0 +1,45 @@
+//! Bookmarked products and their JSON export format.
+use crate::api::AppError;
+use serde::{Deserialize, Serialize};
+
+/// A bookmarked product.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Favorite {
+    pub code: String,
+    pub product_name: Option<String>,
+}
+
+/// Layout of an exported favorites file.
+#[derive(Serialize, Deserialize)]
+struct FavoritesFile {
+    favorites: Vec<Favorite>,
+}
+
+/// Serializes `favorites` for [`import`] on another machine.
+pub fn export(favorites: &[Favorite]) -> String {
+    let file = FavoritesFile {
+        favorites: favorites.to_vec(),
+    };
+    serde_json::to_string_pretty(&file).expect("favorites always serialize")
+}
+
+/// Reads a file written by [`export`], rejecting entries without a barcode.
+pub fn import(json: &str) -> Result<Vec<Favorite>, AppError> {
+    let file: FavoritesFile = serde_json::from_str(json)?;
+    if let Some(index) = file.favorites.iter().position(|favorite| favorite.code.trim().is_empty()) {
+        return Err(AppError::Parse(format!("favorite #{} has no barcode", index + 1)));
+    }
+    Ok(file.favorites)
+}
+
+/// Appends the `imported` favorites that are not in `favorites` yet, matching
+/// them by code, and returns how many were added.
+pub fn merge(favorites: &mut Vec<Favorite>, imported: Vec<Favorite>) -> usize {
+    let before = favorites.len();
+    for favorite in imported {
+        if !favorites.iter().any(|existing| existing.code == favorite.code) {
+            favorites.push(favorite);
+        }
+    }
+    favorites.len() - before
+}
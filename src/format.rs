@@ -0,0 +1,87 @@
+//! Locale-aware formatting of the values shown to the user.
+use chrono::DateTime;
+
+/// Languages writing decimals with a comma (`1,5`) rather than a dot (`1.5`).
+const COMMA_DECIMAL_LOCALES: &[&str] = &["de", "es", "fr", "it", "nl", "pt", "pl", "ru", "sv", "da", "fi", "nb", "cs"];
+
+/// Whether `locale` writes decimals with a comma.
+pub fn uses_decimal_comma(locale: &str) -> bool {
+    COMMA_DECIMAL_LOCALES.contains(&locale)
+}
+
+/// The language of a POSIX locale such as `"fr_FR.UTF-8"` (`"fr"`), or `None`
+/// for the `C`/`POSIX` locales that name none.
+pub fn language_code(posix_locale: &str) -> Option<String> {
+    let language = posix_locale.split(['_', '.', '@']).next()?.to_ascii_lowercase();
+    let is_language = (2..=3).contains(&language.len()) && language.chars().all(|c| c.is_ascii_lowercase());
+    (is_language && language != "c").then_some(language)
+}
+
+/// Formats `value` with `decimals` digits after the locale's decimal separator.
+pub fn fmt_number(value: f64, decimals: usize, locale: &str) -> String {
+    let formatted = format!("{:.*}", decimals, value);
+    if uses_decimal_comma(locale) {
+        formatted.replace('.', ",")
+    } else {
+        formatted
+    }
+}
+
+/// Formats a weight in grams, e.g. `"12.5 g"` or `"12,5 g"`.
+pub fn fmt_grams(value: f64, locale: &str) -> String {
+    format!("{} g", fmt_number(value, 1, locale))
+}
+
+/// Formats a Unix timestamp (in seconds) as a date in the locale's usual style.
+pub fn fmt_date(timestamp: i64, locale: &str) -> String {
+    let Some(date) = DateTime::from_timestamp(timestamp, 0) else {
+        return "N/A".to_string();
+    };
+    let pattern = match locale {
+        "de" | "cs" | "pl" | "ru" | "fi" | "nb" | "da" => "%d.%m.%Y",
+        "fr" | "es" | "it" | "pt" | "nl" => "%d/%m/%Y",
+        _ => "%Y-%m-%d",
+    };
+    date.format(pattern).to_string()
+}
+
+/// Formats a size in bytes with a binary unit, e.g. `"512 B"` or `"14.2 KiB"`.
+pub fn fmt_bytes(bytes: usize) -> String {
+    const UNITS: [&str; 3] = ["KiB", "MiB", "GiB"];
+    if bytes < 1024 {
+        return format!("{} B", bytes);
+    }
+    let mut value = bytes as f64 / 1024.0;
+    let mut unit = 0;
+    while value >= 1024.0 && unit < UNITS.len() - 1 {
+        value /= 1024.0;
+        unit += 1;
+    }
+    format!("{:.1} {}", value, UNITS[unit])
+}
+
+/// Fits `text` on one line: runs of whitespace, newlines included, become a single
+/// space, and text longer than `max_chars` is cut with an ellipsis.
+pub fn single_line(text: &str, max_chars: usize) -> String {
+    let collapsed = text.split_whitespace().collect::<Vec<_>>().join(" ");
+    if collapsed.chars().count() <= max_chars {
+        return collapsed;
+    }
+    let mut truncated: String = collapsed.chars().take(max_chars.saturating_sub(1)).collect();
+    truncated.truncate(truncated.trim_end().len());
+    truncated.push('…');
+    truncated
+}
+
+/// Parses a number typed by the user, accepting both `,` and `.` as the decimal
+/// separator (`"1,5"` and `"1.5"`), but no thousands separators or exponents.
+pub fn parse_locale_f64(s: &str) -> Option<f64> {
+    let s = s.trim();
+    let well_formed = s.chars().all(|c| c.is_ascii_digit() || matches!(c, ',' | '.' | '-'))
+        && s.matches([',', '.']).count() <= 1
+        && s.chars().any(|c| c.is_ascii_digit());
+    if !well_formed {
+        return None;
+    }
+    s.replace(',', ".").parse().ok()
+}
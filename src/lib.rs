@@ -0,0 +1,10 @@
+//! Headless OpenFoodFacts access, shared by the GUI binary and any other tool.
+pub mod api;
+pub mod barcode;
+pub mod cache;
+pub mod credentials;
+pub mod export;
+pub mod favorites;
+pub mod format;
+pub mod links;
+pub mod nutrition;
@@ -0,0 +1,140 @@
+//! Shareable links reopening a product or a search in the app.
+use crate::api::{SearchQuery, SortBy};
+use crate::nutrition::{FilterNutrient, RangeFilter};
+
+/// URL scheme of the links produced by [`product_link`] and [`SearchLink::to_link`].
+pub const SCHEME: &str = "offviewer";
+
+/// Link opening the details of the product with barcode `code`.
+pub fn product_link(code: &str) -> String {
+    format!("{}://product/{}", SCHEME, code)
+}
+
+/// Extracts the barcode from a link built by [`product_link`].
+pub fn parse_product_link(link: &str) -> Option<&str> {
+    let code = link
+        .trim()
+        .strip_prefix(SCHEME)?
+        .strip_prefix("://product/")?
+        .trim_end_matches('/');
+    (!code.is_empty() && code.chars().all(|c| c.is_ascii_digit())).then_some(code)
+}
+
+/// A page of search results that a link can reopen.
+#[derive(Clone, Debug, PartialEq)]
+pub struct SearchLink {
+    pub query: SearchQuery,
+    /// The 1-based page shown.
+    pub page: u32,
+    /// Order the server sorted the results by.
+    pub sort: Option<SortBy>,
+    /// The narrowed nutriment ranges the results were filtered by.
+    pub filters: Vec<RangeFilter>,
+}
+
+impl SearchLink {
+    /// Link such as `offviewer://search?q=greek%20yogurt&page=2`, followed by
+    /// `sort_by=` and the narrowed ends of the filters (`min_sugars=`, `max_salt=`...).
+    pub fn to_link(&self) -> String {
+        let (key, value) = match &self.query {
+            SearchQuery::Terms(terms) => ("q", terms),
+            SearchQuery::Category(tag) => ("category", tag),
+            SearchQuery::Brand(tag) => ("brand", tag),
+        };
+        let mut link = format!("{}://search?{}={}&page={}", SCHEME, key, percent_encode(value), self.page);
+        if let Some(sort) = self.sort {
+            link.push_str(&format!("&sort_by={}", sort.param()));
+        }
+        for filter in self.filters.iter().filter(|filter| filter.is_active()) {
+            let key = filter.nutrient.key();
+            if filter.min > 0.0 {
+                link.push_str(&format!("&min_{}={}", key, filter.min));
+            }
+            if filter.max < filter.nutrient.max_value() {
+                link.push_str(&format!("&max_{}={}", key, filter.max));
+            }
+        }
+        link
+    }
+
+    /// Reads a link built by [`SearchLink::to_link`]; unknown parameters are ignored.
+    pub fn parse(link: &str) -> Option<Self> {
+        let params = link.trim().strip_prefix(SCHEME)?.strip_prefix("://search?")?;
+        let mut query = None;
+        let mut page = 1;
+        let mut sort = None;
+        let mut filters: Vec<RangeFilter> = Vec::new();
+        for param in params.split('&') {
+            let (key, value) = param.split_once('=')?;
+            let value = percent_decode(value)?;
+            match key {
+                "q" => query = Some(SearchQuery::Terms(value)),
+                "category" => query = Some(SearchQuery::Category(value)),
+                "brand" => query = Some(SearchQuery::Brand(value)),
+                "page" => page = value.parse().ok().filter(|&page| page >= 1)?,
+                "sort_by" => sort = Some(SortBy::from_param(&value)?),
+                _ => {
+                    let Some((is_min, nutrient)) = filter_bound(key) else {
+                        continue;
+                    };
+                    let bound = value.parse().ok().filter(|bound| (0.0..=nutrient.max_value()).contains(bound))?;
+                    let filter = match filters.iter().position(|filter| filter.nutrient == nutrient) {
+                        Some(index) => &mut filters[index],
+                        None => {
+                            filters.push(RangeFilter::new(nutrient));
+                            filters.last_mut()?
+                        }
+                    };
+                    if is_min {
+                        filter.min = bound;
+                    } else {
+                        filter.max = bound;
+                    }
+                }
+            }
+        }
+        Some(Self {
+            query: query?,
+            page,
+            sort,
+            filters,
+        })
+    }
+}
+
+/// Reads a `min_<nutrient>` or `max_<nutrient>` parameter name, telling whether it is the minimum.
+fn filter_bound(key: &str) -> Option<(bool, FilterNutrient)> {
+    let (is_min, key) = match key.strip_prefix("min_") {
+        Some(key) => (true, key),
+        None => (false, key.strip_prefix("max_")?),
+    };
+    let nutrient = FilterNutrient::ALL.into_iter().find(|nutrient| nutrient.key() == key)?;
+    Some((is_min, nutrient))
+}
+
+/// Percent-encodes everything but the unreserved characters of RFC 3986.
+pub(crate) fn percent_encode(text: &str) -> String {
+    text.bytes()
+        .map(|byte| match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => char::from(byte).to_string(),
+            _ => format!("%{:02X}", byte),
+        })
+        .collect()
+}
+
+/// Reverses [`percent_encode`], failing on malformed escapes or invalid UTF-8.
+fn percent_decode(text: &str) -> Option<String> {
+    let mut bytes = Vec::with_capacity(text.len());
+    let mut rest = text.as_bytes();
+    while let Some((&byte, tail)) = rest.split_first() {
+        if byte == b'%' {
+            let hex = std::str::from_utf8(tail.get(..2)?).ok()?;
+            bytes.push(u8::from_str_radix(hex, 16).ok()?);
+            rest = &tail[2..];
+        } else {
+            bytes.push(byte);
+            rest = tail;
+        }
+    }
+    String::from_utf8(bytes).ok()
+}
@@ -1,213 +1,3400 @@
 use eframe::egui;
-use serde::Deserialize;
-use std::sync::mpsc;
 use log::{debug, error, info};
+use std::collections::{HashMap, VecDeque};
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{mpsc, Arc};
+use std::time::{Duration, Instant};
+use test_egui::api::{self, ApiConfig, AppError, Flavor, OpenFoodFactsClient, Product, ProductDetails, RequestHooks, SearchQuery};
+use test_egui::barcode;
+use test_egui::cache::{self, DetailsCache};
+use test_egui::credentials;
+use test_egui::favorites::{self, Favorite};
+use test_egui::nutrition::{self, FilterNutrient, NutritionBasis, RangeFilter};
+use test_egui::links::{self, SearchLink};
+use test_egui::{export, format};
 
-// Structs for API responses
-#[derive(Deserialize, Debug)]
-struct Product {
-    code: Option<String>,
-    product_name: Option<String>,
+/// Name of the application, also used to locate its data directory.
+const APP_NAME: &str = "OpenFoodFacts Viewer";
+/// File of the data directory holding the product details cache.
+const CACHE_FILE: &str = "details_cache.json";
+/// Storage key of the lifetime of cached products, in hours.
+const CACHE_TTL_HOURS_KEY: &str = "cache_ttl_hours";
+/// How long a toast notification stays on screen.
+const TOAST_DURATION: Duration = Duration::from_secs(4);
+/// Moves the keyboard focus to the search box (Ctrl+L, or Cmd+L on macOS).
+const FOCUS_SEARCH: egui::KeyboardShortcut = egui::KeyboardShortcut::new(egui::Modifiers::COMMAND, egui::Key::L);
+/// macOS-style alternative to [`FOCUS_SEARCH`].
+const FOCUS_SEARCH_MAC: egui::KeyboardShortcut = egui::KeyboardShortcut::new(egui::Modifiers::MAC_CMD, egui::Key::F);
+const TOGGLE_SHORTCUTS: egui::KeyboardShortcut = egui::KeyboardShortcut::new(egui::Modifiers::NONE, egui::Key::F1);
+const TOGGLE_REQUEST_LOG: egui::KeyboardShortcut =
+    egui::KeyboardShortcut::new(egui::Modifiers::COMMAND.plus(egui::Modifiers::SHIFT), egui::Key::D);
+/// Shortcuts listed in the help overlay.
+const SHORTCUTS: &[(egui::KeyboardShortcut, &str)] = &[
+    (FOCUS_SEARCH, "Focus the search box"),
+    (FOCUS_SEARCH_MAC, "Focus the search box (macOS)"),
+    (TOGGLE_SHORTCUTS, "Show or hide this help"),
+    (TOGGLE_REQUEST_LOG, "Show or hide the request log"),
+];
+/// License of the OpenFoodFacts database, linked from the footer.
+const ODBL_URL: &str = "https://opendatacommons.org/licenses/odbl/1-0/";
+/// Storage key of the number of results per page.
+const PAGE_SIZE_KEY: &str = "page_size";
+/// Storage key of the number of attempts made for each request.
+const RETRY_ATTEMPTS_KEY: &str = "retry_attempts";
+/// Storage key of the username of the login; its password lives in the OS keyring.
+const AUTH_USERNAME_KEY: &str = "auth_username";
+/// Storage key of whether the login is sent to the production servers too.
+const ALWAYS_AUTHENTICATE_KEY: &str = "always_authenticate";
+/// Storage key of whether the staging servers are queried.
+const USE_STAGING_KEY: &str = "use_staging";
+/// Storage key of the language of the product data and of the number and date formats.
+const LOCALE_KEY: &str = "locale";
+/// Languages offered in the settings, as `(code, name)` pairs.
+const LOCALES: [(&str, &str); 8] = [
+    ("en", "English"),
+    ("fr", "Français"),
+    ("de", "Deutsch"),
+    ("es", "Español"),
+    ("it", "Italiano"),
+    ("nl", "Nederlands"),
+    ("pt", "Português"),
+    ("pl", "Polski"),
+];
+/// Most attempts the settings allow per request.
+const MAX_RETRY_ATTEMPTS: u32 = 5;
+/// Storage key of the allergens the user wants to be warned about.
+const ALLERGEN_WATCHLIST_KEY: &str = "allergen_watchlist";
+/// Number of requests kept in the request log.
+const REQUEST_LOG_LIMIT: usize = 200;
+/// Longest product name shown on a result button before it is cut.
+const MAX_RESULT_NAME_CHARS: usize = 80;
+/// Default delay between two automatic refreshes of a product.
+const DEFAULT_REFRESH_MINUTES: u32 = 5;
+/// How long the search box must stay unchanged before its text is searched for.
+const SEARCH_DEBOUNCE: Duration = Duration::from_millis(400);
+/// Distance from the bottom of the results at which the next page starts loading.
+const LOAD_MORE_MARGIN: f32 = 100.0;
+/// Width of a result in the grid layout.
+const GRID_CELL_WIDTH: f32 = 120.0;
+/// Longest product name shown under a result in the grid layout.
+const GRID_NAME_CHARS: usize = 16;
+/// Side of the box reserved for each search result's thumbnail.
+const THUMBNAIL_SIZE: f32 = 48.0;
+/// Page sizes offered in the top panel.
+const PAGE_SIZES: [u32; 4] = [10, 20, 50, 100];
+/// Delay between two connectivity checks while offline.
+const CONNECTIVITY_POLL_INTERVAL: Duration = Duration::from_secs(10);
+/// Number of recently viewed products kept in the history panel.
+const HISTORY_LIMIT: usize = 20;
+/// Storage key of the recently viewed products.
+const HISTORY_KEY: &str = "history";
+/// Storage key of the bookmarked products.
+const FAVORITES_KEY: &str = "favorites";
+/// Number of recent search terms offered in the top panel.
+const SEARCH_HISTORY_LIMIT: usize = 20;
+/// Storage key of the recent search terms.
+const SEARCH_HISTORY_KEY: &str = "search_history";
+/// Storage key of the text left in the search box.
+const SEARCH_TERM_KEY: &str = "search_term";
+
+// Application state
+enum View {
+    SearchResults,
+    ProductDetails,
+    Comparison,
+    /// The bookmarked products.
+    Favorites,
 }
 
-#[derive(Deserialize)]
-struct SearchResponse {
-    products: Vec<Product>,
+/// A product fetched in the background, shown as a comparison column or a tab.
+enum ProductSlot {
+    Loading,
+    Loaded(Box<ProductDetails>),
+    Failed(AppError),
 }
 
-#[derive(Deserialize)]
-struct ProductDetails {
-    code: String,
-    product_name: Option<String>,
-    ingredients_text: Option<String>,
-    brands: Option<String>,
-    // other fields...
+/// Download state of a product image, keyed by URL.
+enum ImageState {
+    /// Fraction received so far, `None` when the size is unknown.
+    Loading(Option<f32>),
+    Loaded(egui::load::Bytes),
+    Failed(AppError),
 }
 
-#[derive(Deserialize)]
-struct ProductDetailsResponse {
-    product: ProductDetails,
+/// Which details request, if any, the UI is currently waiting for.
+#[derive(PartialEq)]
+enum LoadingState {
+    None,
+    Details,
 }
 
-// Application state
-enum View {
-    SearchResults,
-    ProductDetails,
+/// The search panels of the split view; `Main` is the only one otherwise.
+#[derive(Clone, Copy, PartialEq)]
+enum Pane {
+    Main,
+    Split,
+}
+
+/// One independent search: its query, the page of results shown and the request in flight.
+struct SearchState {
+    term: String,
+    /// The query behind `results`.
+    active_query: Option<SearchQuery>,
+    /// Order the server sorts every result of `active_query` by.
+    server_sort: Option<api::SortBy>,
+    results: Vec<Product>,
+    /// The 1-based page of `active_query` that `results` start with.
+    first_page: u32,
+    /// The last page of `active_query` in `results`, past `first_page` once scrolled to the bottom.
+    page: u32,
+    page_count: u32,
+    /// Total number of products matching `active_query`, if the server said.
+    result_count: Option<u64>,
+    /// Value of the "jump to page" field.
+    jump_to_page: u32,
+    loading: bool,
+    /// Incremented by every new search, so that the answers to abandoned ones can be told apart.
+    request_id: u64,
+    /// Whether the page after `page` is being fetched, to be appended to `results`.
+    loading_more: bool,
+    cancel: Arc<AtomicBool>,
+}
+
+impl Default for SearchState {
+    fn default() -> Self {
+        Self {
+            term: String::new(),
+            active_query: None,
+            server_sort: None,
+            results: Vec::new(),
+            first_page: 1,
+            page: 1,
+            page_count: 1,
+            result_count: None,
+            jump_to_page: 1,
+            loading: false,
+            request_id: 0,
+            loading_more: false,
+            cancel: Arc::new(AtomicBool::new(false)),
+        }
+    }
+}
+
+impl SearchState {
+    /// Marks `page` of `query` as loading, cancelling the previous request.
+    fn begin(&mut self, query: SearchQuery, page: u32) {
+        self.active_query = Some(query);
+        self.first_page = page;
+        self.page = page;
+        self.jump_to_page = page;
+        self.loading = true;
+        self.loading_more = false;
+        self.request_id += 1;
+        renew_cancel_flag(&mut self.cancel);
+    }
+
+    /// Marks the page after the last one shown as loading, unless there is none
+    /// or a request is already in flight; returns what to fetch.
+    fn begin_next_page(&mut self) -> Option<(SearchQuery, u32)> {
+        if self.loading || self.loading_more || self.page >= self.page_count {
+            return None;
+        }
+        let query = self.active_query.clone()?;
+        self.loading_more = true;
+        Some((query, self.page + 1))
+    }
+
+    /// Shows a page fetched after [`Self::begin`], or appends it after [`Self::begin_next_page`].
+    fn finish(&mut self, response: api::SearchResponse) {
+        self.page_count = response.page_count();
+        self.result_count = response.count;
+        if self.loading_more {
+            self.page += 1;
+            self.results.extend(response.products);
+        } else {
+            self.results = response.products;
+        }
+        self.loading = false;
+        self.loading_more = false;
+    }
+
+    fn fail(&mut self) {
+        self.loading = false;
+        self.loading_more = false;
+    }
+
+    /// "Results for ..." line shown under the heading.
+    fn subtitle(&self) -> Option<String> {
+        let query = self.active_query.as_ref()?;
+        let mut subtitle = format!("Results for {}", query.describe());
+        if !self.loading {
+            let count = self.result_count.unwrap_or(self.results.len() as u64);
+            subtitle.push_str(&format!(" — {} products", count));
+            if self.first_page < self.page {
+                subtitle.push_str(&format!(", pages {}–{} of {}", self.first_page, self.page, self.page_count));
+            } else if self.page_count > 1 {
+                subtitle.push_str(&format!(", page {} of {}", self.page, self.page_count));
+            }
+        }
+        Some(subtitle)
+    }
+
+    /// Renders the previous/next and "jump to page" controls; returns the page to load.
+    fn render_pagination(&mut self, ui: &mut egui::Ui) -> Option<u32> {
+        let mut requested_page = None;
+        ui.add_enabled_ui(self.page_count > 1, |ui| {
+            ui.horizontal(|ui| {
+                if ui.add_enabled(self.first_page > 1, egui::Button::new("◀ Previous")).clicked() {
+                    requested_page = Some(self.first_page - 1);
+                }
+                ui.label(format!("Page {} of {}", self.page, self.page_count));
+                if ui.add_enabled(self.page < self.page_count, egui::Button::new("Next ▶")).clicked() {
+                    requested_page = Some(self.page + 1);
+                }
+                ui.separator();
+                let label = ui.label("Go to page");
+                let field = ui
+                    .add(egui::DragValue::new(&mut self.jump_to_page).range(1..=self.page_count))
+                    .labelled_by(label.id);
+                let submitted = field.lost_focus() && ui.input(|i| i.key_pressed(egui::Key::Enter));
+                if ui.button("Go").clicked() || submitted {
+                    requested_page = Some(self.jump_to_page.clamp(1, self.page_count));
+                }
+            });
+        });
+        requested_page
+    }
 }
 
+/// Results sent by the worker threads to the UI.
+///
+/// Workers ignore send errors: the receiver is dropped when the window closes
+/// while they are still running.
 enum Message {
-    SearchResults(Vec<Product>),
-    ProductDetails(ProductDetails),
-    Error(String),
+    /// A page of results for the search with the given request id.
+    SearchResults(Pane, u64, api::SearchResponse),
+    /// The search with the given request id failed.
+    SearchError(Pane, u64, AppError),
+    /// Details fetched for the given navigation generation.
+    ProductDetails(u64, Box<ProductDetails>),
+    /// A details fetch failed for the given navigation generation.
+    DetailsError(u64, AppError),
+    Toast(String),
+    /// A product of the comparison started at the given generation, keyed by code.
+    ComparedProduct(u64, String, Result<Box<ProductDetails>, AppError>),
+    /// A product opened from the split search panel, keyed by code.
+    SplitProduct(String, Result<Box<ProductDetails>, AppError>),
+    /// A product opened in a background tab, keyed by code.
+    TabProduct(String, Result<Box<ProductDetails>, AppError>),
+    /// Fresh details of an open product, fetched past the cache.
+    RefreshedProduct(String, Result<Box<ProductDetails>, AppError>),
+    /// Part of an image has been received.
+    ImageProgress { url: String, fraction: Option<f32> },
+    Image { url: String, result: Result<Vec<u8>, AppError> },
+    /// Result of a connectivity check made while offline.
+    Connectivity(bool),
+    /// A download finished, for the request log.
+    RequestLogged(api::RequestRecord),
+}
+
+/// File formats the search results can be exported to.
+#[derive(Clone, Copy)]
+enum ExportFormat {
+    Csv,
+    Json,
+}
+
+/// Refreshes of one open product, in the details view or a tab.
+struct RefreshState {
+    /// Whether the product is fetched again every `interval_minutes`.
+    auto: bool,
+    interval_minutes: u32,
+    last_refresh: Option<Instant>,
+    in_flight: bool,
+}
+
+impl Default for RefreshState {
+    fn default() -> Self {
+        Self {
+            auto: false,
+            interval_minutes: DEFAULT_REFRESH_MINUTES,
+            last_refresh: None,
+            in_flight: false,
+        }
+    }
+}
+
+impl RefreshState {
+    /// Time left before the next automatic refresh, zero when it is due.
+    fn until_due(&self) -> Duration {
+        let interval = Duration::from_secs(u64::from(self.interval_minutes) * 60);
+        self.last_refresh.map_or(Duration::ZERO, |last| interval.saturating_sub(last.elapsed()))
+    }
+}
+
+/// What the text of the search box is looked up as.
+#[derive(Clone, Copy, Default, PartialEq)]
+enum SearchMode {
+    /// Keywords of a product search.
+    #[default]
+    Terms,
+    /// The barcode of a product, opened directly.
+    Barcode,
+}
+
+/// How the search results are laid out.
+#[derive(Clone, Copy, Default, PartialEq)]
+enum ResultLayout {
+    /// One product per row, with its comparison checkbox.
+    #[default]
+    List,
+    /// Thumbnails with their name, filling the width.
+    Grid,
+}
+
+/// How the search results are ordered.
+#[derive(Clone, Copy, Default, PartialEq)]
+enum ResultSort {
+    /// As the server returned them.
+    #[default]
+    Relevance,
+    /// By name, within the current page.
+    NameAscending,
+    NameDescending,
+    /// By the server, over all the results.
+    Server(api::SortBy),
+}
+
+impl ResultSort {
+    fn label(self) -> String {
+        match self {
+            ResultSort::Relevance => "Relevance".to_string(),
+            ResultSort::NameAscending => "Name (A–Z)".to_string(),
+            ResultSort::NameDescending => "Name (Z–A)".to_string(),
+            ResultSort::Server(sort) => format!("☁ {}", sort.label()),
+        }
+    }
+
+    fn server_sort(self) -> Option<api::SortBy> {
+        match self {
+            ResultSort::Server(sort) => Some(sort),
+            _ => None,
+        }
+    }
+}
+
+/// What the command line asks to open at startup.
+enum StartupRequest {
+    /// The details of the product with this barcode.
+    Product(String),
+    Search(SearchLink),
+}
+
+/// The last request sent, kept so that it can be retried.
+#[derive(Clone)]
+enum LastRequest {
+    /// A query and the page of it that was requested.
+    Search(SearchQuery, u32),
+    Details(String),
+}
+
+/// Destructive actions that wait for the user's confirmation before running.
+#[derive(Clone, Copy)]
+enum ConfirmAction {
+    ClearHistory,
+}
+
+impl ConfirmAction {
+    fn prompt(self) -> &'static str {
+        match self {
+            ConfirmAction::ClearHistory => "Clear the list of recently viewed products?",
+        }
+    }
+}
+
+struct Toast {
+    text: String,
+    shown_at: Instant,
+}
+
+/// Cancels whatever request currently watches `flag` and replaces it with a fresh one.
+fn renew_cancel_flag(flag: &mut Arc<AtomicBool>) {
+    flag.store(true, Ordering::Relaxed);
+    *flag = Arc::new(AtomicBool::new(false));
+}
+
+/// Background and text colors of an `a`..`e` grade badge, following the official
+/// Nutri-Score palette; any other character gives the grey "unknown" badge.
+///
+/// The fill is dimmed in dark mode so it does not glare, and the text is black
+/// or white, whichever contrasts best with the fill.
+fn grade_colors(grade: char, visuals: &egui::Visuals) -> (egui::Color32, egui::Color32) {
+    let fill = match grade {
+        'a' => egui::Color32::from_rgb(0x03, 0x81, 0x41),
+        'b' => egui::Color32::from_rgb(0x85, 0xbb, 0x2f),
+        'c' => egui::Color32::from_rgb(0xfe, 0xcb, 0x02),
+        'd' => egui::Color32::from_rgb(0xee, 0x81, 0x00),
+        'e' => egui::Color32::from_rgb(0xe6, 0x3e, 0x11),
+        _ => egui::Color32::GRAY,
+    };
+    let fill = if visuals.dark_mode {
+        let [r, g, b, _] = fill.to_array();
+        let dim = |channel: u8| (u16::from(channel) * 4 / 5) as u8;
+        egui::Color32::from_rgb(dim(r), dim(g), dim(b))
+    } else {
+        fill
+    };
+    // Perceived brightness (ITU-R BT.601), enough to pick a readable text color
+    let [r, g, b, _] = fill.to_array();
+    let brightness = 0.299 * f32::from(r) + 0.587 * f32::from(g) + 0.114 * f32::from(b);
+    let text = if brightness > 150.0 { egui::Color32::BLACK } else { egui::Color32::WHITE };
+    (fill, text)
+}
+
+/// Explanation of one of the scores shown as badges.
+struct ScoreSystem {
+    name: &'static str,
+    /// One-line description, also used as the badge tooltip.
+    summary: &'static str,
+    explanation: &'static str,
+    /// Each possible value with the grade color it is drawn with.
+    legend: &'static [(&'static str, char)],
+}
+
+const NUTRISCORE: ScoreSystem = ScoreSystem {
+    name: "Nutri-Score",
+    summary: "Nutritional quality, from A (best) to E (worst)",
+    explanation: "Computed from the energy, sugars, saturated fat and salt of 100g of product, \
+        balanced by its fiber, proteins, fruits and vegetables. Compare products of the same kind with it.",
+    legend: &[("A", 'a'), ("B", 'b'), ("C", 'c'), ("D", 'd'), ("E", 'e')],
+};
+
+const NOVA: ScoreSystem = ScoreSystem {
+    name: "NOVA",
+    summary: "Degree of processing, from 1 (unprocessed) to 4 (ultra-processed)",
+    explanation: "1: unprocessed or minimally processed foods. 2: processed culinary ingredients (oils, sugar...). \
+        3: processed foods. 4: ultra-processed foods, made with industrial ingredients and additives.",
+    legend: &[("1", 'a'), ("2", 'c'), ("3", 'd'), ("4", 'e')],
+};
+
+const ECOSCORE: ScoreSystem = ScoreSystem {
+    name: "Eco-Score",
+    summary: "Environmental impact, from A (lowest) to E (highest)",
+    explanation: "Based on a life cycle analysis of the product category, adjusted for the labels, \
+        origins of the ingredients and packaging of this product.",
+    legend: &[("A", 'a'), ("B", 'b'), ("C", 'c'), ("D", 'd'), ("E", 'e')],
+};
+
+/// Draws a single score badge; unknown scores are shown as a grey "?".
+fn score_badge(ui: &mut egui::Ui, title: &str, value: Option<String>, grade: Option<char>, tooltip: &str) {
+    let (value, grade, tooltip) = match (value, grade) {
+        (Some(value), Some(grade)) => (value, grade, tooltip.to_string()),
+        _ => ("?".to_string(), '?', format!("{} (unknown for this product)", tooltip)),
+    };
+    let (fill, text) = grade_colors(grade, ui.visuals());
+    // The outline keeps pale badges distinct from a light panel background
+    let outline = ui.visuals().widgets.noninteractive.bg_stroke;
+    egui::Frame::new()
+        .fill(fill)
+        .stroke(outline)
+        .corner_radius(4.0)
+        .inner_margin(egui::Margin::symmetric(8, 4))
+        .show(ui, |ui| {
+            ui.label(egui::RichText::new(format!("{} {}", title, value).trim()).strong().color(text));
+        })
+        .response
+        .on_hover_text(tooltip);
+}
+
+/// Renders the nutrition table with its basis and % DV toggles; returns whether it was copied.
+fn render_nutrition(
+    ui: &mut egui::Ui,
+    product: &ProductDetails,
+    basis: &mut NutritionBasis,
+    show_daily_values: &mut bool,
+    locale: &str,
+) -> bool {
+    let rows = nutrition::nutrient_rows(&product.nutriments);
+    if rows.iter().all(|row| row.per_100g.is_none()) {
+        return false;
+    }
+    let serving = product.serving_size.as_deref().and_then(nutrition::serving_base_amount);
+    let kcal_factor = nutrition::per_100kcal_factor(product.nutriments.energy_kcal_100g);
+    if (*basis == NutritionBasis::PerServing && serving.is_none()) || (*basis == NutritionBasis::Per100kcal && kcal_factor.is_none()) {
+        *basis = NutritionBasis::Per100g;
+    }
+    let mut copied = false;
+    ui.horizontal(|ui| {
+        ui.strong("Nutrition facts");
+        ui.selectable_value(basis, NutritionBasis::Per100g, "Per 100g");
+        ui.add_enabled_ui(serving.is_some(), |ui| {
+            ui.selectable_value(basis, NutritionBasis::PerServing, "Per serving")
+                .on_disabled_hover_text("The serving size of this product is unknown");
+        });
+        ui.add_enabled_ui(kcal_factor.is_some(), |ui| {
+            ui.selectable_value(basis, NutritionBasis::Per100kcal, "Per 100 kcal")
+                .on_hover_text("Compare foods by nutrients per calorie")
+                .on_disabled_hover_text("The energy of this product is unknown");
+        });
+        ui.toggle_value(show_daily_values, "% DV")
+            .on_hover_text("Compare each value to a daily reference intake");
+        if ui.button("📋 Copy as TSV").on_hover_text("Copy the table for a spreadsheet").clicked() {
+            ui.ctx().copy_text(nutrition::nutrition_tsv(&rows, serving, *basis, locale));
+            copied = true;
+        }
+    });
+    egui::Grid::new("nutrition_grid").striped(true).show(ui, |ui| {
+        for row in &rows {
+            let value = match (*basis, serving, kcal_factor) {
+                (NutritionBasis::PerServing, Some(serving), _) => row.per_serving(serving),
+                (NutritionBasis::Per100kcal, _, Some(factor)) => row.per_100kcal(factor),
+                _ => row.per_100g,
+            };
+            ui.label(row.name);
+            ui.label(value.map_or("N/A".to_string(), |value| {
+                format!("{} {}", format::fmt_number(value, 1, locale), row.unit)
+            }));
+            if *show_daily_values {
+                let percent = value.and_then(|value| nutrition::percent_daily_value(row.name, value));
+                ui.label(percent.map_or(String::new(), |percent| format!("{} %", format::fmt_number(percent, 0, locale))));
+            }
+            ui.end_row();
+        }
+    });
+    if *show_daily_values {
+        ui.small("% DV compares to generic adult reference intakes (2000 kcal), not personal needs.");
+    }
+    copied
+}
+
+/// Minimum height of buttons and other interactive widgets, so they are easy to hit.
+const MIN_HIT_HEIGHT: f32 = 28.0;
+
+/// A button showing a product `name` on a single line, with the full name on hover when it had to be shortened.
+fn result_button(ui: &mut egui::Ui, name: &str) -> egui::Response {
+    let line = format::single_line(name, MAX_RESULT_NAME_CHARS);
+    let shortened = line != name;
+    let response = ui.button(line);
+    response.widget_info(|| egui::WidgetInfo::labeled(egui::WidgetType::Button, true, format::single_line(name, usize::MAX)));
+    if shortened {
+        response.on_hover_text(name)
+    } else {
+        response
+    }
+}
+
+/// Whether `scroll` shows the end of its content, or close to it, so that the next page should be loaded.
+fn scrolled_to_bottom<R>(scroll: &egui::scroll_area::ScrollAreaOutput<R>) -> bool {
+    let max_offset = (scroll.content_size.y - scroll.inner_rect.height()).max(0.0);
+    scroll.state.offset.y >= max_offset - LOAD_MORE_MARGIN
+}
+
+/// Prefixes `name` with "⚠ " when the product contains one of the `watched`
+/// allergens, returning the hover text naming them.
+fn allergen_flag(name: &str, watched: &[&str]) -> (String, Option<String>) {
+    if watched.is_empty() {
+        (name.to_string(), None)
+    } else {
+        (format!("⚠ {}", name), Some(format!("Contains watched allergen: {}", watched.join(", "))))
+    }
+}
+
+/// The checkbox picking the product `code` for comparison.
+fn compare_checkbox(ui: &mut egui::Ui, selection: &mut Vec<String>, code: &str, name: &str) {
+    let mut selected = selection.iter().any(|selected_code| selected_code == code);
+    let checkbox = ui.checkbox(&mut selected, "").on_hover_text("Compare");
+    checkbox.widget_info(|| egui::WidgetInfo::selected(egui::WidgetType::Checkbox, true, selected, format!("Compare {}", name)));
+    if checkbox.changed() {
+        if selected {
+            selection.push(code.to_string());
+        } else {
+            selection.retain(|selected_code| selected_code != code);
+        }
+    }
+}
+
+/// How a click on a result asks to open it: `Some(true)` in a background tab
+/// (middle-click or Ctrl/Cmd+click, keeping the list open), `Some(false)` in place.
+fn result_click(ui: &egui::Ui, response: &egui::Response) -> Option<bool> {
+    if response.clicked_by(egui::PointerButton::Middle) || (response.clicked() && ui.input(|i| i.modifiers.command)) {
+        Some(true)
+    } else if response.clicked() {
+        Some(false)
+    } else {
+        None
+    }
+}
+
+/// Moves `term` to the front of the recent searches, dropping older duplicates.
+fn remember_search(history: &mut Vec<String>, term: &str) {
+    if term.is_empty() {
+        return;
+    }
+    history.retain(|entry| entry != term);
+    history.insert(0, term.to_string());
+    history.truncate(SEARCH_HISTORY_LIMIT);
+}
+
+/// Draws the thumbnail at `url` in a fixed [`THUMBNAIL_SIZE`] box, so rows do
+/// not move when it arrives: a spinner while downloading, an empty box without image.
+fn thumbnail(ui: &mut egui::Ui, images: &HashMap<String, ImageState>, url: Option<&str>) {
+    let (rect, _) = ui.allocate_exact_size(egui::Vec2::splat(THUMBNAIL_SIZE), egui::Sense::hover());
+    match url.and_then(|url| Some((url, images.get(url)?))) {
+        Some((url, ImageState::Loaded(bytes))) => {
+            egui::Image::from_bytes(format!("bytes://{}", url), bytes.clone())
+                .show_loading_spinner(true)
+                .paint_at(ui, rect);
+        }
+        state => {
+            ui.painter().rect_filled(rect, 4.0, ui.visuals().faint_bg_color);
+            if url.is_some() && matches!(state, None | Some((_, ImageState::Loading(_)))) {
+                ui.put(rect.shrink(THUMBNAIL_SIZE / 4.0), egui::Spinner::new());
+            }
+        }
+    }
+}
+
+/// Enlarges the hit targets of `style` to at least `MIN_HIT_HEIGHT`.
+fn apply_accessible_spacing(style: &mut egui::Style) {
+    style.spacing.interact_size.y = style.spacing.interact_size.y.max(MIN_HIT_HEIGHT);
+    style.spacing.button_padding = egui::vec2(8.0, 4.0);
+}
+
+/// Number of chips shown before a list is collapsed behind a "+N more" button.
+const CHIP_LIMIT: usize = 12;
+
+/// How many chips of a list of `total` are shown, all of them once expanded.
+fn visible_chip_count(total: usize, expanded: bool) -> usize {
+    if expanded {
+        total
+    } else {
+        total.min(CHIP_LIMIT)
+    }
+}
+
+/// Renders the brand, quantity, grade and categories of `product`, "N/A" standing for the missing ones.
+fn render_facts(ui: &mut egui::Ui, product: &ProductDetails) {
+    let categories = product.categories_label();
+    let grade = product.nutrition_grades.as_deref().map(str::to_uppercase);
+    let facts = [
+        ("Brands", product.brands.as_deref()),
+        ("Quantity", product.quantity.as_deref()),
+        ("Nutrition grade", grade.as_deref()),
+        ("Categories", categories.as_deref()),
+    ];
+    egui::Grid::new(("facts", &product.code)).num_columns(2).show(ui, |ui| {
+        for (title, value) in facts {
+            ui.strong(format!("{}:", title));
+            let value = value.map(str::trim).filter(|value| !value.is_empty()).unwrap_or("N/A");
+            ui.add(egui::Label::new(value).selectable(true).wrap());
+            ui.end_row();
+        }
+    });
+}
+
+/// Renders taxonomy tags as chips flowing onto several lines, collapsing long lists.
+fn render_chips(ui: &mut egui::Ui, title: &str, tags: &[String]) {
+    if tags.is_empty() {
+        return;
+    }
+    let id = ui.id().with(("chips", title));
+    let mut expanded = ui.data(|data| data.get_temp::<bool>(id)).unwrap_or(false);
+    ui.strong(format!("{}:", title));
+    ui.horizontal_wrapped(|ui| {
+        let shown = visible_chip_count(tags.len(), expanded);
+        for tag in &tags[..shown] {
+            // A single widget (rather than a frame) so the wrapped layout can move it to the next line
+            let chip = egui::RichText::new(format!(" {} ", api::tag_label(tag))).background_color(ui.visuals().faint_bg_color);
+            ui.add(egui::Label::new(chip).extend());
+        }
+        if shown < tags.len() {
+            if ui.button(format!("+{} more", tags.len() - shown)).clicked() {
+                expanded = true;
+            }
+        } else if tags.len() > CHIP_LIMIT && ui.button("Show less").clicked() {
+            expanded = false;
+        }
+    });
+    ui.data_mut(|data| data.insert_temp(id, expanded));
 }
 
 struct OpenFoodFactsViewer {
-    search_term: String,
-    search_results: Vec<Product>,
+    config: ApiConfig,
+    /// Answers the searches and product lookups, replaced by canned answers in tests.
+    client: Arc<dyn OpenFoodFactsClient>,
+    flavor: Flavor,
+    /// Whether the staging server of `flavor` is queried instead of the production one.
+    use_staging: bool,
+    /// Login fields of the settings, saved to the keyring on demand.
+    auth_username: String,
+    auth_password: String,
+    search: SearchState,
+    search_mode: SearchMode,
+    /// When the search box was last typed in, until its text is searched for.
+    search_edited: Option<Instant>,
+    /// Order of the main search results.
+    result_sort: ResultSort,
+    result_layout: ResultLayout,
+    /// Nutriment ranges the loaded results are filtered by.
+    range_filters: Vec<RangeFilter>,
+    /// Text of the min and max fields of each of `range_filters`.
+    range_filter_inputs: Vec<[String; 2]>,
+    /// Whether filtered results include products whose filtered nutriments are unknown.
+    include_unknown_nutriments: bool,
+    /// Whether a second, independent search panel is shown next to the main one.
+    split_view: bool,
+    split: SearchState,
+    /// Product opened from the split panel, shown in place of its results.
+    split_details: Option<(String, ProductSlot)>,
     selected_product: Option<ProductDetails>,
+    details_cache: DetailsCache,
+    /// Where `details_cache` is saved on exit, if the platform has a data directory.
+    cache_path: Option<PathBuf>,
+    /// How long cached products stay valid.
+    cache_ttl_hours: u64,
+    nutrition_basis: NutritionBasis,
+    /// Whether the nutrition table has a "% DV" column.
+    show_daily_values: bool,
     view: View,
-    is_loading: bool,
-    error_message: Option<String>,
+    loading: LoadingState,
+    error_message: Option<AppError>,
+    toasts: Vec<Toast>,
+    history: VecDeque<Product>,
+    show_history: bool,
+    /// Terms searched for, most recent first.
+    search_history: Vec<String>,
+    favorites: Vec<Favorite>,
+    /// Whether the details view was opened from the favorites, so that "Back" returns there.
+    details_from_favorites: bool,
+    pending_confirm: Option<ConfirmAction>,
+    show_shortcuts: bool,
+    /// Recent downloads, newest last, with the time they finished.
+    request_log: VecDeque<(Instant, api::RequestRecord)>,
+    show_request_log: bool,
+    show_about: bool,
+    show_score_help: bool,
+    show_settings: bool,
+    /// Allergens flagged loudly on matching products, e.g. `"nuts"`.
+    allergen_watchlist: Vec<String>,
+    /// Text of the "add allergen" field in the settings.
+    new_allergen: String,
+    last_request: Option<LastRequest>,
+    /// Set while requests fail because the server cannot be reached.
+    offline: bool,
+    /// When connectivity was last checked, `None` while a check is running.
+    last_connectivity_check: Option<Instant>,
+    details_cancel: Arc<AtomicBool>,
+    /// Bumped whenever the user navigates in or out of details, so late answers can be dropped.
+    details_generation: u64,
+    /// Codes of the search results picked for comparison.
+    compare_selection: Vec<String>,
+    /// Columns of the comparison view, in selection order.
+    comparison: Vec<(String, ProductSlot)>,
+    comparison_cancel: Arc<AtomicBool>,
+    comparison_generation: u64,
+    /// Products opened in background tabs, in opening order.
+    tabs: Vec<(String, ProductSlot)>,
+    /// Manual and automatic refreshes of the open products, by code.
+    refreshes: HashMap<String, RefreshState>,
+    images: HashMap<String, ImageState>,
+    /// Cancels the requests that outlive navigation (tabs, images) when the app exits.
+    background_cancel: Arc<AtomicBool>,
     message_sender: mpsc::Sender<Message>,
     message_receiver: mpsc::Receiver<Message>,
 }
 
 impl OpenFoodFactsViewer {
-    fn new(_cc: &eframe::CreationContext<'_>) -> Self {
+    fn new(cc: &eframe::CreationContext<'_>, config: ApiConfig, startup_request: Option<StartupRequest>) -> Self {
+        cc.egui_ctx.all_styles_mut(apply_accessible_spacing);
+        egui_extras::install_image_loaders(&cc.egui_ctx);
+        let cache_path = eframe::storage_dir(APP_NAME).map(|dir| dir.join(CACHE_FILE));
+        let mut viewer = Self::with_client(cc.storage, config, Arc::new(api::ReqwestClient), cache_path);
+        match startup_request {
+            Some(StartupRequest::Product(code)) => viewer.open_product(code),
+            Some(StartupRequest::Search(link)) => viewer.open_search_link(link),
+            None => {}
+        }
+        viewer
+    }
+
+    /// Restores the settings saved in `storage` and the product cache saved at
+    /// `cache_path`, sending the requests through `client`.
+    fn with_client(storage: Option<&dyn eframe::Storage>, mut config: ApiConfig, client: Arc<dyn OpenFoodFactsClient>, cache_path: Option<PathBuf>) -> Self {
         let (sender, receiver) = mpsc::channel();
+        let history = storage
+            .and_then(|storage| eframe::get_value(storage, HISTORY_KEY))
+            .unwrap_or_default();
+        let favorites = storage
+            .and_then(|storage| eframe::get_value(storage, FAVORITES_KEY))
+            .unwrap_or_default();
+        let search_history = storage
+            .and_then(|storage| eframe::get_value(storage, SEARCH_HISTORY_KEY))
+            .unwrap_or_default();
+        let search_term = storage
+            .and_then(|storage| eframe::get_value(storage, SEARCH_TERM_KEY))
+            .unwrap_or_default();
+        let details_cache = cache_path.as_deref().map(DetailsCache::load).unwrap_or_default();
+        let cache_ttl_hours = storage
+            .and_then(|storage| eframe::get_value(storage, CACHE_TTL_HOURS_KEY))
+            .unwrap_or(cache::DEFAULT_TTL.as_secs() / 3600);
+        let allergen_watchlist = storage
+            .and_then(|storage| eframe::get_value(storage, ALLERGEN_WATCHLIST_KEY))
+            .unwrap_or_default();
+        if let Some(page_size) = storage.and_then(|storage| eframe::get_value::<u32>(storage, PAGE_SIZE_KEY)) {
+            config.page_size = page_size.clamp(1, api::MAX_PAGE_SIZE);
+        }
+        if let Some(locale) = storage.and_then(|storage| eframe::get_value::<String>(storage, LOCALE_KEY)) {
+            config.locale = locale;
+        }
+        if let Some(attempts) = storage.and_then(|storage| eframe::get_value::<u32>(storage, RETRY_ATTEMPTS_KEY)) {
+            config.retry_attempts = attempts.clamp(1, MAX_RETRY_ATTEMPTS);
+        }
+        let use_staging = storage.and_then(|storage| eframe::get_value(storage, USE_STAGING_KEY)).unwrap_or(false);
+        if use_staging {
+            config.base_url = Flavor::default().staging_url();
+        }
+        config.always_authenticate = storage
+            .and_then(|storage| eframe::get_value(storage, ALWAYS_AUTHENTICATE_KEY))
+            .unwrap_or(false);
+        let auth_username: String = storage
+            .and_then(|storage| eframe::get_value(storage, AUTH_USERNAME_KEY))
+            .unwrap_or_default();
+        config.credentials = credentials::load(&auth_username);
+        let auth_password = config.credentials.as_ref().map(|login| login.password.clone()).unwrap_or_default();
         Self {
-            search_term: String::new(),
-            search_results: Vec::new(),
+            config,
+            client,
+            flavor: Flavor::default(),
+            use_staging,
+            auth_username,
+            auth_password,
+            search: SearchState {
+                term: search_term,
+                ..Default::default()
+            },
+            search_mode: SearchMode::default(),
+            search_edited: None,
+            result_sort: ResultSort::default(),
+            result_layout: ResultLayout::default(),
+            range_filters: FilterNutrient::ALL.into_iter().map(RangeFilter::new).collect(),
+            range_filter_inputs: vec![Default::default(); FilterNutrient::ALL.len()],
+            include_unknown_nutriments: false,
+            split_view: false,
+            split: SearchState::default(),
+            split_details: None,
             selected_product: None,
+            details_cache,
+            cache_path,
+            cache_ttl_hours,
+            nutrition_basis: NutritionBasis::default(),
+            show_daily_values: false,
             view: View::SearchResults,
-            is_loading: false,
+            loading: LoadingState::None,
             error_message: None,
+            toasts: Vec::new(),
+            history,
+            show_history: false,
+            search_history,
+            favorites,
+            details_from_favorites: false,
+            pending_confirm: None,
+            show_shortcuts: false,
+            request_log: VecDeque::new(),
+            show_request_log: false,
+            show_about: false,
+            show_score_help: false,
+            show_settings: false,
+            allergen_watchlist,
+            new_allergen: String::new(),
+            last_request: None,
+            offline: false,
+            last_connectivity_check: Some(Instant::now()),
+            details_cancel: Arc::new(AtomicBool::new(false)),
+            details_generation: 0,
+            compare_selection: Vec::new(),
+            comparison: Vec::new(),
+            comparison_cancel: Arc::new(AtomicBool::new(false)),
+            comparison_generation: 0,
+            tabs: Vec::new(),
+            refreshes: HashMap::new(),
+            images: HashMap::new(),
+            background_cancel: Arc::new(AtomicBool::new(false)),
             message_sender: sender,
             message_receiver: receiver,
         }
     }
-}
 
-impl eframe::App for OpenFoodFactsViewer {
-    fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
-        // Top panel with search bar
-        egui::TopBottomPanel::top("top_panel").show(ctx, |ui| {
-            ui.horizontal(|ui| {
-                ui.label("Search:");
-                let response = ui.text_edit_singleline(&mut self.search_term);
-                if ui.button("Search").clicked() || (response.lost_focus() && ui.input(|i: &egui::InputState| i.key_pressed(egui::Key::Enter))) {
-                    self.is_loading = true;
-                    self.error_message = None;
-                    let sender = self.message_sender.clone();
-                    let search_term = self.search_term.clone();
-                    std::thread::spawn(move || {
-                        let url = format!(
-                            "https://world.openfoodfacts.org/cgi/search.pl?search_terms={}&search_simple=1&json=1",
-                            search_term
-                        );
-                        debug!("Requesting search results from: {}", url);
-                        match reqwest::blocking::get(&url) {
-                            Ok(response) => {
-                                debug!("Received response with status: {}", response.status());
-                                let response_text = response.text().unwrap();
-                                // println!("Raw JSON: {}", response_text);
-                                let search_response: SearchResponse = serde_json::from_str(&response_text).unwrap();
-                                println!("Products: {:?}", search_response.products);
-                                // Cloner le texte de la réponse avant de déplacer `response`
-                                let response_json = serde_json::from_str::<serde_json::Value>(&response_text).unwrap();
-                                match serde_json::from_value::<SearchResponse>(response_json) {
-                                    Ok(search_response) => {
-                                        info!("Successfully parsed search results.");
-                                        sender.send(Message::SearchResults(search_response.products)).unwrap();
-                                    }
-                                    Err(e) => {
-                                        error!("Failed to parse response: {}", e);
-                                        sender.send(Message::Error(format!("Failed to parse response: {}", e))).unwrap();
-                                    }
-                                }
-                            }
-                            Err(e) => {
-                                error!("Failed to get response: {}", e);
-                                sender.send(Message::Error(format!("Failed to get response: {}", e))).unwrap();
-                            }
-                        }
-                    });
+    /// Builds the hooks for a request that is cancelled through `cancel`.
+    fn request_hooks(&self, cancel: &Arc<AtomicBool>) -> RequestHooks {
+        let sender = self.message_sender.clone();
+        let log_sender = self.message_sender.clone();
+        RequestHooks {
+            cancel: cancel.clone(),
+            on_rate_limit: Some(Arc::new(move |secs| {
+                let _ = sender.send(Message::Toast(format!("Rate limited, retrying in {}s", secs)));
+            })),
+            on_request: Some(Arc::new(move |record| {
+                let _ = log_sender.send(Message::RequestLogged(record));
+            })),
+            wait: None,
+        }
+    }
+
+    /// Searches for the terms of the search box.
+    fn submit_search(&mut self) {
+        let term = self.search.term.trim().to_string();
+        remember_search(&mut self.search_history, &term);
+        self.search_edited = None;
+        self.start_search(SearchQuery::Terms(term));
+    }
+
+    /// The barcode typed in the search box in barcode mode, or found in pasted
+    /// text such as a receipt line or a product URL.
+    fn barcode_input(&self) -> Option<String> {
+        let text = self.search.term.trim();
+        barcode::extract_code(text).or_else(|| (!text.is_empty() && text.chars().all(|c| c.is_ascii_digit())).then(|| text.to_string()))
+    }
+
+    /// Opens the product of [`Self::barcode_input`], if any.
+    fn submit_barcode(&mut self) {
+        if let Some(code) = self.barcode_input() {
+            self.open_product(code);
+        }
+    }
+
+    /// Restores the sort and filters of a search link, and loads its page of results.
+    fn open_search_link(&mut self, link: SearchLink) {
+        if let SearchQuery::Terms(terms) = &link.query {
+            self.search.term = terms.clone();
+        }
+        self.result_sort = link.sort.map_or(ResultSort::Relevance, ResultSort::Server);
+        self.search.server_sort = link.sort;
+        for (filter, inputs) in self.range_filters.iter_mut().zip(&mut self.range_filter_inputs) {
+            *filter = link
+                .filters
+                .iter()
+                .find(|linked| linked.nutrient == filter.nutrient)
+                .cloned()
+                .unwrap_or_else(|| RangeFilter::new(filter.nutrient));
+            // Open ends stay empty, as when typed
+            let min = if filter.min > 0.0 { filter.min.to_string() } else { String::new() };
+            let max = if filter.max < filter.nutrient.max_value() { filter.max.to_string() } else { String::new() };
+            *inputs = [min, max];
+        }
+        self.load_search_page(link.query, link.page);
+    }
+
+    /// Runs `query` in the background and shows its first page of results.
+    fn start_search(&mut self, query: SearchQuery) {
+        self.load_search_page(query, 1);
+    }
+
+    /// Runs `query` in the background and shows the given page of its results.
+    fn load_search_page(&mut self, query: SearchQuery, page: u32) {
+        match self.view {
+            View::ProductDetails => self.close_product(),
+            View::Comparison => self.close_comparison(),
+            View::SearchResults | View::Favorites => {}
+        }
+        self.view = View::SearchResults;
+        self.last_request = Some(LastRequest::Search(query.clone(), page));
+        self.error_message = None;
+        self.run_search(Pane::Main, query, page);
+    }
+
+    fn search_state(&mut self, pane: Pane) -> &mut SearchState {
+        match pane {
+            Pane::Main => &mut self.search,
+            Pane::Split => &mut self.split,
+        }
+    }
+
+    /// Fetches `page` of `query` into the search panel `pane`.
+    fn run_search(&mut self, pane: Pane, query: SearchQuery, page: u32) {
+        self.search_state(pane).begin(query.clone(), page);
+        self.spawn_search(pane, query, page);
+    }
+
+    /// Appends the next page of results to the search panel `pane`, if there is one
+    /// and no request for it is in flight yet.
+    fn load_next_page(&mut self, pane: Pane) {
+        if let Some((query, page)) = self.search_state(pane).begin_next_page() {
+            self.spawn_search(pane, query, page);
+        }
+    }
+
+    /// Fetches `page` of `query` in the background for the search panel `pane`.
+    fn spawn_search(&mut self, pane: Pane, query: SearchQuery, page: u32) {
+        let sender = self.message_sender.clone();
+        let config = self.config.clone();
+        let client = self.client.clone();
+        let cancel = self.search_state(pane).cancel.clone();
+        let hooks = self.request_hooks(&cancel);
+        let sort = self.search_state(pane).server_sort;
+        let request_id = self.search_state(pane).request_id;
+        std::thread::spawn(move || {
+            let message = match client.search(&config, &query, page, sort, &hooks) {
+                Ok(response) => Message::SearchResults(pane, request_id, response),
+                Err(AppError::Cancelled) => return,
+                Err(e) => Message::SearchError(pane, request_id, e),
+            };
+            let _ = sender.send(message);
+        });
+    }
+
+    /// Fetches a product into the sub-view of the split panel.
+    fn open_split_product(&mut self, code: String) {
+        if let Some(details) = self.details_cache.get(&self.cache_source(), &code, self.cache_ttl(), cache::now()) {
+            let slot = ProductSlot::Loaded(Box::new(details.clone()));
+            self.split_details = Some((code, slot));
+            return;
+        }
+        self.split_details = Some((code.clone(), ProductSlot::Loading));
+        let sender = self.message_sender.clone();
+        let config = self.config.clone();
+        let client = self.client.clone();
+        let hooks = self.request_hooks(&self.split.cancel);
+        std::thread::spawn(move || {
+            let result = match client.product(&config, &code, &hooks) {
+                Err(AppError::Cancelled) => return,
+                result => result.map(Box::new),
+            };
+            let _ = sender.send(Message::SplitProduct(code, result));
+        });
+    }
+
+    /// Switches to the details view and fetches the product in the background.
+    fn open_product(&mut self, code: String) {
+        match self.view {
+            View::Favorites => self.details_from_favorites = true,
+            View::ProductDetails => {}
+            View::SearchResults | View::Comparison => self.details_from_favorites = false,
+        }
+        self.view = View::ProductDetails;
+        self.loading = LoadingState::Details;
+        self.error_message = None;
+        self.last_request = Some(LastRequest::Details(code.clone()));
+        self.details_generation += 1;
+        let generation = self.details_generation;
+        if let Some(details) = self.details_cache.get(&self.cache_source(), &code, self.cache_ttl(), cache::now()) {
+            debug!("Showing cached details for {}", code);
+            let details = details.clone();
+            self.record_history(details.summary());
+            self.selected_product = Some(details);
+            self.loading = LoadingState::None;
+            return;
+        }
+        let sender = self.message_sender.clone();
+        let config = self.config.clone();
+        let client = self.client.clone();
+        renew_cancel_flag(&mut self.details_cancel);
+        let hooks = self.request_hooks(&self.details_cancel);
+        std::thread::spawn(move || {
+            let message = match client.product(&config, &code, &hooks) {
+                Ok(details) => Message::ProductDetails(generation, Box::new(details)),
+                Err(AppError::Cancelled) => return,
+                Err(e) => Message::DetailsError(generation, e),
+            };
+            let _ = sender.send(message);
+        });
+    }
+
+    /// Downloads the image at `url` in the background, reporting its progress.
+    fn fetch_image(&mut self, ctx: &egui::Context, url: String) {
+        self.images.insert(url.clone(), ImageState::Loading(None));
+        let sender = self.message_sender.clone();
+        let config = self.config.clone();
+        let hooks = self.request_hooks(&self.background_cancel);
+        let ctx = ctx.clone();
+        std::thread::spawn(move || {
+            let mut last_percent = None;
+            let result = api::download_image(&config, &url, &hooks, |fraction| {
+                // One message per percent is plenty for a progress bar
+                let percent = fraction.map(|fraction| (fraction * 100.0) as u32);
+                if last_percent != Some(percent) {
+                    last_percent = Some(percent);
+                    let _ = sender.send(Message::ImageProgress { url: url.clone(), fraction });
+                    ctx.request_repaint();
                 }
             });
+            let _ = sender.send(Message::Image { url, result });
+            ctx.request_repaint();
         });
+    }
 
-        // Central panel for content
-        egui::CentralPanel::default().show(ctx, |ui| {
-            if self.is_loading {
-                ui.label("Loading...");
-            } else if let Some(error) = &self.error_message {
-                ui.label(format!("Error: {}", error));
-            } else {
-                match self.view {
-                    View::SearchResults => {
-                        ui.heading("Search Results");
-                        egui::ScrollArea::vertical().show(ui, |ui| {
-                            for product in &self.search_results {
-                                if ui.button(product.product_name.as_deref().unwrap_or("Unnamed product")).clicked() {
-                                    self.view = View::ProductDetails;
-                                    self.is_loading = true;
-                                    let sender = self.message_sender.clone();
-                                    let code = product.code.clone().unwrap_or_else(|| "unknown".to_string());
-                                    std::thread::spawn(move || {
-                                        let url = format!(
-                                            "https://world.openfoodfacts.org/api/v0/product/{}.json",
-                                            code
-                                        );
-                                        debug!("Requesting product details from: {}", url);
-                                        match reqwest::blocking::get(&url) {
-                                            Ok(response) => {
-                                                debug!("Received response with status: {}", response.status());
-                                                match response.json::<ProductDetailsResponse>() {
-                                                    Ok(details_response) => {
-                                                        info!("Successfully parsed product details.");
-                                                        sender.send(Message::ProductDetails(details_response.product)).unwrap();
-                                                    }
-                                                    Err(e) => {
-                                                        error!("Failed to parse details: {}", e);
-                                                        sender.send(Message::Error(format!("Failed to parse details: {}", e))).unwrap();
-                                                    }
-                                                }
-                                            }
-                                            Err(e) => {
-                                                error!("Details request failed: {}", e);
-                                                sender.send(Message::Error(format!("Details request failed: {}", e))).unwrap();
-                                            }
-                                        }
-                                    });
-                                }
-                            }
-                        });
+    /// Renders the image at `url`, or a placeholder with the download progress.
+    ///
+    /// Right-clicking the image offers to open it in the browser or to save it;
+    /// returns whether saving was requested.
+    fn render_image(&self, ui: &mut egui::Ui, url: &str) -> bool {
+        let placeholder = egui::vec2(200.0, 200.0);
+        let mut save = false;
+        match self.images.get(url) {
+            Some(ImageState::Loaded(bytes)) => {
+                let response = ui
+                    .add(egui::Image::from_bytes(format!("bytes://{}", url), bytes.clone()).max_size(placeholder).sense(egui::Sense::click()))
+                    .on_hover_text("Right-click for options");
+                response.context_menu(|ui| {
+                    if ui.button("Open in browser").clicked() {
+                        ui.ctx().open_url(egui::OpenUrl::new_tab(url));
+                        ui.close_menu();
+                    }
+                    if ui.button("Save image…").clicked() {
+                        save = true;
+                        ui.close_menu();
                     }
-                    View::ProductDetails => {
-                        if let Some(product) = &self.selected_product {
-                            ui.heading(product.product_name.as_deref().unwrap_or("Unnamed product"));
-                            ui.label(format!(
-                                "Ingredients: {}",
-                                product.ingredients_text.as_ref().unwrap_or(&"N/A".to_string())
-                            ));
+                });
+            }
+            Some(ImageState::Loading(fraction)) => {
+                egui::Frame::new().fill(ui.visuals().faint_bg_color).show(ui, |ui| {
+                    ui.set_min_size(placeholder);
+                    ui.set_max_width(placeholder.x);
+                    ui.centered_and_justified(|ui| match fraction {
+                        Some(fraction) => {
+                            ui.add(egui::ProgressBar::new(*fraction).show_percentage());
                         }
-                        if ui.button("Back").clicked() {
-                            self.view = View::SearchResults;
-                            self.selected_product = None;
+                        None => {
+                            ui.spinner();
                         }
-                    }
-                }
+                    });
+                });
+            }
+            Some(ImageState::Failed(err)) => {
+                ui.weak(format!("Image unavailable: {}", err));
+            }
+            None => {}
+        }
+        save
+    }
+
+    /// Asks where to save the downloaded image at `url`, and writes it there without downloading it again.
+    fn save_image(&mut self, url: &str) {
+        let Some(ImageState::Loaded(bytes)) = self.images.get(url) else {
+            return;
+        };
+        let file_name = url.rsplit('/').next().filter(|name| !name.is_empty()).unwrap_or("image.jpg");
+        let mut dialog = rfd::FileDialog::new().set_file_name(file_name);
+        if let Some((_, extension)) = file_name.rsplit_once('.') {
+            dialog = dialog.add_filter("Image", &[extension]);
+        }
+        let Some(path) = dialog.save_file() else {
+            return;
+        };
+        match std::fs::write(&path, &bytes[..]) {
+            Ok(()) => self.notify(format!("Saved {}", path.display())),
+            Err(e) => {
+                error!("Failed to save image to {}: {}", path.display(), e);
+                self.notify(format!("Could not save the image: {}", e));
+            }
+        }
+    }
+
+    /// Schedules downloads of the thumbnails among `urls` not requested yet.
+    fn fetch_thumbnails(&mut self, ctx: &egui::Context, urls: Vec<String>) {
+        for url in urls {
+            if !self.images.contains_key(&url) {
+                self.fetch_image(ctx, url);
             }
+        }
+    }
+
+    fn cache_ttl(&self) -> Duration {
+        Duration::from_secs(self.cache_ttl_hours * 3600)
+    }
+
+    /// The server and language the details cache is keyed by.
+    fn cache_source(&self) -> String {
+        format!("{} {}", self.config.base_url, self.config.locale)
+    }
+
+    /// Leaves the details view, abandoning any details fetch still in flight.
+    fn close_product(&mut self) {
+        self.details_generation += 1;
+        self.details_cancel.store(true, Ordering::Relaxed);
+        self.view = if self.details_from_favorites { View::Favorites } else { View::SearchResults };
+        self.selected_product = None;
+        self.loading = LoadingState::None;
+        self.error_message = None;
+    }
+
+    /// Switches to the comparison view and fetches every selected product concurrently.
+    fn open_comparison(&mut self) {
+        self.view = View::Comparison;
+        self.error_message = None;
+        self.comparison_generation += 1;
+        renew_cancel_flag(&mut self.comparison_cancel);
+        self.comparison = self
+            .compare_selection
+            .iter()
+            .map(|code| (code.clone(), ProductSlot::Loading))
+            .collect();
+        for code in self.compare_selection.clone() {
+            let generation = self.comparison_generation;
+            let sender = self.message_sender.clone();
+            let config = self.config.clone();
+            let client = self.client.clone();
+            let hooks = self.request_hooks(&self.comparison_cancel);
+            std::thread::spawn(move || {
+                let result = match client.product(&config, &code, &hooks) {
+                    Err(AppError::Cancelled) => return,
+                    result => result.map(Box::new),
+                };
+                let _ = sender.send(Message::ComparedProduct(generation, code, result));
+            });
+        }
+    }
+
+    fn close_comparison(&mut self) {
+        self.comparison_generation += 1;
+        self.comparison_cancel.store(true, Ordering::Relaxed);
+        self.comparison.clear();
+        self.view = View::SearchResults;
+    }
+
+    /// Fetches a product into a background tab, leaving the current view alone.
+    fn open_in_tab(&mut self, code: String) {
+        if self.tabs.iter().any(|(tab_code, _)| *tab_code == code) {
+            return;
+        }
+        if let Some(details) = self.details_cache.get(&self.cache_source(), &code, self.cache_ttl(), cache::now()) {
+            let slot = ProductSlot::Loaded(Box::new(details.clone()));
+            self.tabs.push((code, slot));
+            return;
+        }
+        self.tabs.push((code.clone(), ProductSlot::Loading));
+        let sender = self.message_sender.clone();
+        let config = self.config.clone();
+        let client = self.client.clone();
+        let hooks = self.request_hooks(&self.background_cancel);
+        std::thread::spawn(move || {
+            let result = match client.product(&config, &code, &hooks) {
+                Err(AppError::Cancelled) => return,
+                result => result.map(Box::new),
+            };
+            let _ = sender.send(Message::TabProduct(code, result));
         });
+    }
 
-        // Handle messages from background threads
-        while let Ok(message) = self.message_receiver.try_recv() {
-            match message {
-                Message::SearchResults(results) => {
-                    self.search_results = results;
-                    self.is_loading = false;
-                }
-                Message::ProductDetails(details) => {
-                    self.selected_product = Some(details);
-                    self.is_loading = false;
+    /// Shows a loaded tab in the details view.
+    fn show_tab(&mut self, details: ProductDetails) {
+        match self.view {
+            View::ProductDetails => self.close_product(),
+            View::Comparison => self.close_comparison(),
+            View::SearchResults | View::Favorites => {}
+        }
+        self.details_from_favorites = matches!(self.view, View::Favorites);
+        self.view = View::ProductDetails;
+        self.last_request = Some(LastRequest::Details(details.code.clone()));
+        self.record_history(details.summary());
+        self.selected_product = Some(details);
+    }
+
+    /// Renders the strip of background tabs, if any are open.
+    fn render_tabs(&mut self, ui: &mut egui::Ui) {
+        if self.tabs.is_empty() {
+            return;
+        }
+        let mut shown = None;
+        let mut closed = None;
+        ui.horizontal_wrapped(|ui| {
+            for (code, slot) in &self.tabs {
+                let active = matches!(self.view, View::ProductDetails)
+                    && self.selected_product.as_ref().is_some_and(|product| product.code == *code);
+                match slot {
+                    ProductSlot::Loading => {
+                        ui.spinner();
+                        ui.label(code);
+                    }
+                    ProductSlot::Failed(err) => {
+                        ui.label(format!("{}: {}", code, err));
+                    }
+                    ProductSlot::Loaded(product) => {
+                        let name = product.product_name.as_deref().unwrap_or("Unnamed product");
+                        if ui.selectable_label(active, name).clicked() {
+                            shown = Some((**product).clone());
+                        }
+                        let refresh = self.refreshes.entry(code.clone()).or_default();
+                        let hint = format!("Auto-refresh every {} min", refresh.interval_minutes);
+                        let toggle = ui.toggle_value(&mut refresh.auto, "⟳").on_hover_text(&hint);
+                        toggle.widget_info(|| egui::WidgetInfo::selected(egui::WidgetType::Checkbox, true, refresh.auto, format!("{} of tab {}", hint, code)));
+                        if toggle.changed() && refresh.last_refresh.is_none() {
+                            // Count the interval from now, like the toggle of the details view
+                            refresh.last_refresh = Some(Instant::now());
+                        }
+                    }
                 }
-                Message::Error(err) => {
-                    self.error_message = Some(err);
-                    self.is_loading = false;
+                let close = ui.button("✖").on_hover_text("Close tab");
+                close.widget_info(|| egui::WidgetInfo::labeled(egui::WidgetType::Button, true, format!("Close tab {}", code)));
+                if close.clicked() {
+                    closed = Some(code.clone());
                 }
             }
+        });
+        ui.separator();
+        if let Some(details) = shown {
+            self.show_tab(details);
+        }
+        if let Some(code) = closed {
+            self.tabs.retain(|(tab_code, _)| *tab_code != code);
         }
     }
-}
-
-fn main() {
-    // Initialize the logger
-    env_logger::init();
+
+    /// Sends the last request again.
+    fn retry(&mut self) {
+        match self.last_request.clone() {
+            Some(LastRequest::Search(query, page)) => self.load_search_page(query, page),
+            Some(LastRequest::Details(code)) => self.open_product(code),
+            None => {}
+        }
+    }
+
+    /// Shows `err` to the user; connectivity problems go to the offline banner.
+    fn show_error(&mut self, err: AppError) {
+        self.loading = LoadingState::None;
+        if err == AppError::Offline {
+            if !self.offline && self.last_connectivity_check.is_some() {
+                self.last_connectivity_check = Some(Instant::now());
+            }
+            self.offline = true;
+        } else {
+            self.error_message = Some(err);
+        }
+    }
+
+    /// Fetches the product with this `code` again, bypassing the cache.
+    fn refresh_product(&mut self, code: String) {
+        let state = self.refreshes.entry(code.clone()).or_default();
+        if state.in_flight {
+            return;
+        }
+        state.in_flight = true;
+        let sender = self.message_sender.clone();
+        let config = self.config.clone();
+        let client = self.client.clone();
+        // Like tabs, refreshes share the concurrency limit and are cancelled on exit
+        let hooks = self.request_hooks(&self.background_cancel);
+        std::thread::spawn(move || {
+            let result = match client.product(&config, &code, &hooks) {
+                Err(AppError::Cancelled) => return,
+                result => result.map(Box::new),
+            };
+            let _ = sender.send(Message::RefreshedProduct(code, result));
+        });
+    }
+
+    /// Searches for the text of the search box once it stopped changing for
+    /// [`SEARCH_DEBOUNCE`], and wakes up to check again until then.
+    fn poll_live_search(&mut self, ctx: &egui::Context) {
+        let Some(edited) = self.search_edited else {
+            return;
+        };
+        let remaining = SEARCH_DEBOUNCE.saturating_sub(edited.elapsed());
+        if !remaining.is_zero() {
+            ctx.request_repaint_after(remaining);
+            return;
+        }
+        self.search_edited = None;
+        let term = self.search.term.trim();
+        let query = SearchQuery::Terms(term.to_string());
+        if term.is_empty() || self.search_mode != SearchMode::Terms || self.search.active_query.as_ref() == Some(&query) {
+            return;
+        }
+        self.start_search(query);
+    }
+
+    /// Starts the automatic refreshes that are due, and wakes up for the next one.
+    fn poll_auto_refresh(&mut self, ctx: &egui::Context) {
+        if self.offline {
+            return;
+        }
+        let shown = self.selected_product.as_ref().filter(|_| matches!(self.view, View::ProductDetails));
+        let open: Vec<&str> = shown
+            .map(|product| product.code.as_str())
+            .into_iter()
+            .chain(self.tabs.iter().map(|(code, _)| code.as_str()))
+            .collect();
+        let mut due = Vec::new();
+        for (code, state) in &self.refreshes {
+            if !state.auto || state.in_flight || !open.contains(&code.as_str()) {
+                continue;
+            }
+            match state.until_due() {
+                Duration::ZERO => due.push(code.clone()),
+                remaining => ctx.request_repaint_after(remaining),
+            }
+        }
+        for code in due {
+            self.refresh_product(code);
+        }
+    }
+
+    /// While offline, regularly checks whether the server is reachable again.
+    fn poll_connectivity(&mut self, ctx: &egui::Context) {
+        if !self.offline {
+            return;
+        }
+        if let Some(last_check) = self.last_connectivity_check {
+            let elapsed = last_check.elapsed();
+            if elapsed < CONNECTIVITY_POLL_INTERVAL {
+                ctx.request_repaint_after(CONNECTIVITY_POLL_INTERVAL - elapsed);
+                return;
+            }
+            self.last_connectivity_check = None;
+            let sender = self.message_sender.clone();
+            let config = self.config.clone();
+            let ctx = ctx.clone();
+            std::thread::spawn(move || {
+                let _ = sender.send(Message::Connectivity(api::is_reachable(&config)));
+                ctx.request_repaint();
+            });
+        }
+    }
+
+    fn render_offline_banner(&mut self, ctx: &egui::Context) {
+        if !self.offline {
+            return;
+        }
+        egui::TopBottomPanel::top("offline_banner").show(ctx, |ui| {
+            ui.horizontal(|ui| {
+                ui.colored_label(ui.visuals().warn_fg_color, "⚠ You appear to be offline.");
+                if ui.button("Retry").clicked() {
+                    self.offline = false;
+                    self.retry();
+                }
+            });
+        });
+    }
+
+    /// Moves `product` to the front of the history, dropping older duplicates.
+    fn record_history(&mut self, product: Product) {
+        self.history.retain(|entry| entry.code != product.code);
+        self.history.push_front(product);
+        self.history.truncate(HISTORY_LIMIT);
+    }
+
+    /// Adds `product` to the favorites, or removes it if it is already one.
+    fn toggle_favorite(&mut self, product: Product) {
+        let Some(code) = product.code else {
+            return;
+        };
+        if self.favorites.iter().any(|favorite| favorite.code == code) {
+            self.favorites.retain(|favorite| favorite.code != code);
+        } else {
+            self.favorites.push(Favorite {
+                code,
+                product_name: product.product_name,
+            });
+        }
+    }
+
+    /// Asks where to save the favorites and writes them there as JSON.
+    fn export_favorites(&mut self) {
+        let Some(path) = rfd::FileDialog::new()
+            .add_filter("JSON", &["json"])
+            .set_file_name("favorites.json")
+            .save_file()
+        else {
+            return;
+        };
+        match std::fs::write(&path, favorites::export(&self.favorites)) {
+            Ok(()) => self.notify(format!("Exported {} favorites", self.favorites.len())),
+            Err(e) => {
+                error!("Failed to export favorites to {}: {}", path.display(), e);
+                self.notify(format!("Could not export favorites: {}", e));
+            }
+        }
+    }
+
+    /// Asks for a file written by [`Self::export_favorites`] and merges it into the favorites.
+    fn import_favorites(&mut self) {
+        let Some(path) = rfd::FileDialog::new().add_filter("JSON", &["json"]).pick_file() else {
+            return;
+        };
+        let imported = std::fs::read_to_string(&path)
+            .map_err(|e| e.to_string())
+            .and_then(|json| favorites::import(&json).map_err(|e| e.to_string()));
+        match imported {
+            Ok(imported) => {
+                let added = favorites::merge(&mut self.favorites, imported);
+                self.notify(format!("Imported {} new favorites", added));
+            }
+            Err(e) => {
+                error!("Failed to import favorites from {}: {}", path.display(), e);
+                self.notify(format!("Could not import favorites: {}", e));
+            }
+        }
+    }
+
+    /// Shows the favorites in place of the current view.
+    fn open_favorites(&mut self) {
+        match self.view {
+            View::ProductDetails => self.close_product(),
+            View::Comparison => self.close_comparison(),
+            View::SearchResults | View::Favorites => {}
+        }
+        self.view = View::Favorites;
+        self.error_message = None;
+    }
+
+    /// Renders the list of favorites, opening them like search results.
+    fn render_favorites(&mut self, ui: &mut egui::Ui) {
+        let mut clicked = None;
+        let mut export = false;
+        let mut import = false;
+        ui.horizontal(|ui| {
+            if ui.button("Back").clicked() {
+                self.view = View::SearchResults;
+            }
+            ui.heading("Favorites");
+        });
+        ui.horizontal(|ui| {
+            export = ui
+                .add_enabled(!self.favorites.is_empty(), egui::Button::new("Export favorites"))
+                .clicked();
+            import = ui.button("Import favorites").clicked();
+        });
+        if self.favorites.is_empty() {
+            ui.label("Star a product to bookmark it.");
+        }
+        ui.separator();
+        egui::ScrollArea::vertical().show(ui, |ui| {
+            for favorite in &self.favorites {
+                let response = result_button(ui, favorite.product_name.as_deref().unwrap_or("Unnamed product"));
+                if let Some(in_tab) = result_click(ui, &response) {
+                    clicked = Some((favorite.code.clone(), in_tab));
+                }
+            }
+        });
+        if export {
+            self.export_favorites();
+        }
+        if import {
+            self.import_favorites();
+        }
+        match clicked {
+            Some((code, true)) => self.open_in_tab(code),
+            Some((code, false)) => self.open_product(code),
+            None => {}
+        }
+    }
+
+    fn render_history(&mut self, ctx: &egui::Context) {
+        let mut clicked_code = None;
+        egui::SidePanel::right("history_panel")
+            .resizable(true)
+            .show_animated(ctx, self.show_history, |ui| {
+                ui.heading("Recently viewed");
+                if self.history.is_empty() {
+                    ui.label("No products viewed yet.");
+                } else if ui.button("Clear history").clicked() {
+                    self.confirm(ConfirmAction::ClearHistory);
+                }
+                ui.separator();
+                egui::ScrollArea::vertical().show(ui, |ui| {
+                    for product in &self.history {
+                        if result_button(ui, product.product_name.as_deref().unwrap_or("Unnamed product")).clicked() {
+                            clicked_code = product.code.clone();
+                        }
+                    }
+                });
+            });
+        if let Some(code) = clicked_code {
+            self.open_product(code);
+        }
+    }
+
+    /// Renders the sort menu; server-side sorts re-run the search from its first page.
+    fn render_sort(&mut self, ui: &mut egui::Ui) {
+        let previous = self.result_sort;
+        ui.horizontal(|ui| {
+            let label = ui.label("Sort:");
+            egui::ComboBox::from_id_salt("result_sort")
+                .selected_text(self.result_sort.label())
+                .show_ui(ui, |ui| {
+                    ui.weak("This page");
+                    for sort in [ResultSort::Relevance, ResultSort::NameAscending, ResultSort::NameDescending] {
+                        ui.selectable_value(&mut self.result_sort, sort, sort.label());
+                    }
+                    ui.separator();
+                    ui.weak("All results (server)");
+                    for sort in api::SortBy::ALL.map(ResultSort::Server) {
+                        ui.selectable_value(&mut self.result_sort, sort, sort.label());
+                    }
+                })
+                .response
+                .labelled_by(label.id)
+                .on_hover_text("☁ sorts are applied by the server to every result");
+        });
+        let server_sort = self.result_sort.server_sort();
+        if self.result_sort != previous && server_sort != self.search.server_sort {
+            self.search.server_sort = server_sort;
+            if let Some(query) = self.search.active_query.clone() {
+                self.start_search(query);
+            }
+        }
+    }
+
+    /// Renders the min/max sliders of the nutriment filters.
+    fn render_range_filters(&mut self, ui: &mut egui::Ui) {
+        egui::CollapsingHeader::new("Nutriment filters").show(ui, |ui| {
+            let locale = &self.config.locale;
+            egui::Grid::new("range_filters").show(ui, |ui| {
+                for (filter, inputs) in self.range_filters.iter_mut().zip(&mut self.range_filter_inputs) {
+                    let max_value = filter.nutrient.max_value();
+                    let label = ui.label(format!("{} per 100g", filter.nutrient.label()));
+                    let mut invalid = false;
+                    // An empty field leaves that end of the range open
+                    let bounds = [(0.0, "min"), (max_value, "max")];
+                    for ((input, (default, name)), value) in inputs.iter_mut().zip(bounds).zip([&mut filter.min, &mut filter.max]) {
+                        let parsed = if input.trim().is_empty() {
+                            Some(default)
+                        } else {
+                            format::parse_locale_f64(input).filter(|value| (0.0..=max_value).contains(value))
+                        };
+                        let mut edit = egui::TextEdit::singleline(input)
+                            .hint_text(format!("{} {}", name, format::fmt_number(default, 0, locale)))
+                            .desired_width(70.0);
+                        if parsed.is_none() {
+                            edit = edit.text_color(ui.visuals().error_fg_color);
+                        }
+                        ui.add(edit).labelled_by(label.id);
+                        match parsed {
+                            Some(parsed) => *value = parsed,
+                            None => invalid = true,
+                        }
+                    }
+                    ui.label(filter.nutrient.unit());
+                    if invalid {
+                        ui.colored_label(
+                            ui.visuals().error_fg_color,
+                            format!("Enter a number between 0 and {}", format::fmt_number(max_value, 0, locale)),
+                        );
+                    } else if filter.min > filter.max {
+                        ui.colored_label(ui.visuals().warn_fg_color, "The minimum is above the maximum");
+                    }
+                    ui.end_row();
+                }
+            });
+            ui.horizontal(|ui| {
+                ui.checkbox(&mut self.include_unknown_nutriments, "Include products with unknown values");
+                if ui.button("Reset").clicked() {
+                    for (filter, inputs) in self.range_filters.iter_mut().zip(&mut self.range_filter_inputs) {
+                        *filter = RangeFilter::new(filter.nutrient);
+                        *inputs = Default::default();
+                    }
+                }
+            });
+        });
+    }
+
+    fn render_search_results(&mut self, ui: &mut egui::Ui) {
+        ui.heading("Search Results");
+        if let Some(subtitle) = self.search.subtitle() {
+            ui.weak(subtitle);
+        }
+        if self.search.loading {
+            ui.horizontal(|ui| {
+                ui.spinner();
+                ui.label("Searching...");
+            });
+            return;
+        }
+        let selected_count = self.compare_selection.len();
+        let mut link_copied = false;
+        ui.horizontal(|ui| {
+            let compare = ui
+                .add_enabled(selected_count >= 2, egui::Button::new(format!("Compare ({})", selected_count)))
+                .on_disabled_hover_text("Tick at least two products to compare them");
+            if compare.clicked() {
+                self.open_comparison();
+            }
+            if selected_count > 0 && ui.button("Clear selection").clicked() {
+                self.compare_selection.clear();
+            }
+            if let Some(query) = &self.search.active_query {
+                if ui.button("🔗 Copy link").on_hover_text("Copy a link reopening this page of results").clicked() {
+                    let link = SearchLink {
+                        query: query.clone(),
+                        page: self.search.page,
+                        sort: self.search.server_sort,
+                        filters: self.range_filters.iter().filter(|filter| filter.is_active()).cloned().collect(),
+                    };
+                    ui.ctx().copy_text(link.to_link());
+                    link_copied = true;
+                }
+            }
+        });
+        if link_copied {
+            self.notify("Link copied");
+        }
+        if let Some(query) = self.search.active_query.clone() {
+            if let Some(page) = self.search.render_pagination(ui) {
+                self.load_search_page(query, page);
+            }
+        }
+        self.render_sort(ui);
+        self.render_range_filters(ui);
+        let filtering = self.range_filters.iter().any(RangeFilter::is_active);
+        let mut visible: Vec<&Product> = self
+            .search
+            .results
+            .iter()
+            .filter(|product| {
+                self.range_filters
+                    .iter()
+                    .all(|filter| filter.matches(&product.nutriments, self.include_unknown_nutriments))
+            })
+            .collect();
+        if filtering {
+            ui.weak(format!("Showing {} of {} loaded products", visible.len(), self.search.results.len()));
+        }
+        let name = |product: &&Product| product.product_name.as_deref().unwrap_or_default().to_lowercase();
+        match self.result_sort {
+            ResultSort::NameAscending => visible.sort_by_cached_key(name),
+            ResultSort::NameDescending => visible.sort_by_cached_key(|product| std::cmp::Reverse(name(product))),
+            ResultSort::Relevance | ResultSort::Server(_) => {}
+        }
+        let mut clicked_code = None;
+        let mut background_code = None;
+        let mut thumbnails_to_fetch = Vec::new();
+        if self.search.loading_more {
+            ui.horizontal(|ui| {
+                ui.spinner();
+                ui.label(format!("Loading page {}...", self.search.page + 1));
+            });
+        }
+        let scroll = if self.result_layout == ResultLayout::Grid {
+            egui::ScrollArea::vertical().show(ui, |ui| {
+                ui.horizontal_wrapped(|ui| {
+                    for product in &visible {
+                        let name = product.product_name.as_deref().unwrap_or("Unnamed product");
+                        let watched = api::watched_allergens(&product.allergens_tags, &self.allergen_watchlist);
+                        let url = product.image_thumb_url.as_deref();
+                        if let Some(url) = url.filter(|url| !self.images.contains_key(*url)) {
+                            thumbnails_to_fetch.push(url.to_string());
+                        }
+                        let (label, warning) = allergen_flag(&format::single_line(name, GRID_NAME_CHARS), &watched);
+                        let cell = ui.allocate_ui(egui::vec2(GRID_CELL_WIDTH, THUMBNAIL_SIZE * 2.0), |ui| {
+                            ui.vertical_centered(|ui| {
+                                ui.horizontal(|ui| {
+                                    if let Some(code) = &product.code {
+                                        compare_checkbox(ui, &mut self.compare_selection, code, name);
+                                    }
+                                    thumbnail(ui, &self.images, url);
+                                });
+                                result_button(ui, &label)
+                            })
+                            .inner
+                        });
+                        let hover = match warning {
+                            Some(warning) => format!("{}\n{}", name, warning),
+                            None => name.to_string(),
+                        };
+                        let response = cell.inner.on_hover_text(hover);
+                        let code = product.code.clone().unwrap_or_else(|| "unknown".to_string());
+                        match result_click(ui, &response) {
+                            Some(true) => background_code = Some(code),
+                            Some(false) => clicked_code = Some(code),
+                            None => {}
+                        }
+                    }
+                });
+            })
+        } else {
+            // Every row has the same height, so only the visible ones need laying out
+            let row_height = THUMBNAIL_SIZE.max(ui.spacing().interact_size.y);
+            egui::ScrollArea::vertical().show_rows(ui, row_height, visible.len(), |ui, visible_rows| {
+                for product in &visible[visible_rows] {
+                    let name = product.product_name.as_deref().unwrap_or("Unnamed product");
+                    let watched = api::watched_allergens(&product.allergens_tags, &self.allergen_watchlist);
+                    ui.horizontal(|ui| {
+                        let url = product.image_thumb_url.as_deref();
+                        thumbnail(ui, &self.images, url);
+                        if let Some(url) = url.filter(|url| !self.images.contains_key(*url)) {
+                            thumbnails_to_fetch.push(url.to_string());
+                        }
+                        if let Some(code) = &product.code {
+                            compare_checkbox(ui, &mut self.compare_selection, code, name);
+                        }
+                        let (label, warning) = allergen_flag(name, &watched);
+                        let response = result_button(ui, &label);
+                        let response = match warning {
+                            Some(warning) => response.on_hover_text(warning),
+                            None => response,
+                        };
+                        let code = product.code.clone().unwrap_or_else(|| "unknown".to_string());
+                        match result_click(ui, &response) {
+                            Some(true) => background_code = Some(code),
+                            Some(false) => clicked_code = Some(code),
+                            None => {}
+                        }
+                    });
+                }
+            })
+        };
+        // A short filtered list would otherwise pull in every page one after the other
+        let overflowing = scroll.content_size.y > scroll.inner_rect.height();
+        if scrolled_to_bottom(&scroll) && (overflowing || !filtering) {
+            self.load_next_page(Pane::Main);
+        }
+        self.fetch_thumbnails(ui.ctx(), thumbnails_to_fetch);
+        if let Some(code) = background_code {
+            self.open_in_tab(code);
+        } else if let Some(code) = clicked_code {
+            self.open_product(code);
+        }
+    }
+
+    /// Renders the second search panel of the split view, with its own results and details.
+    fn render_split_panel(&mut self, ctx: &egui::Context) {
+        if !self.split_view {
+            return;
+        }
+        let mut search = false;
+        let mut requested_page = None;
+        let mut clicked_code = None;
+        let mut back = false;
+        let mut score_help_requested = false;
+        egui::SidePanel::right("split_panel")
+            .resizable(true)
+            .default_width(ctx.screen_rect().width() / 2.0)
+            .show(ctx, |ui| {
+                ui.horizontal(|ui| {
+                    let label = ui.label("Search:");
+                    let response = ui
+                        .add(egui::TextEdit::singleline(&mut self.split.term).hint_text("Compare with..."))
+                        .labelled_by(label.id);
+                    let submitted = response.lost_focus() && ui.input(|i| i.key_pressed(egui::Key::Enter));
+                    search = ui.button("Search").clicked() || submitted;
+                });
+                ui.separator();
+                if let Some((code, slot)) = &self.split_details {
+                    back = ui.button("Back").clicked();
+                    egui::ScrollArea::vertical().show(ui, |ui| match slot {
+                        ProductSlot::Loading => {
+                            ui.horizontal(|ui| {
+                                ui.spinner();
+                                ui.label(format!("Loading {}...", code));
+                            });
+                        }
+                        ProductSlot::Failed(err) => {
+                            ui.label(format!("Error: {}", err));
+                        }
+                        ProductSlot::Loaded(product) => {
+                            ui.add(egui::Label::new(egui::RichText::new(product.product_name.as_deref().unwrap_or("Unnamed product")).heading()).wrap());
+                            score_help_requested = self.render_score_header(ui, product);
+                            ui.strong("Ingredients:");
+                            ui.add(egui::Label::new(product.ingredients_text.as_deref().unwrap_or("N/A")).selectable(true).wrap());
+                            render_chips(ui, "Labels", &product.labels_tags);
+                        }
+                    });
+                    return;
+                }
+                if let Some(subtitle) = self.split.subtitle() {
+                    ui.weak(subtitle);
+                }
+                if self.split.loading {
+                    ui.horizontal(|ui| {
+                        ui.spinner();
+                        ui.label("Searching...");
+                    });
+                    return;
+                }
+                if self.split.active_query.is_some() {
+                    requested_page = self.split.render_pagination(ui);
+                }
+                let row_height = ui.spacing().interact_size.y;
+                egui::ScrollArea::vertical().show_rows(ui, row_height, self.split.results.len(), |ui, visible_rows| {
+                    for product in &self.split.results[visible_rows] {
+                        let name = product.product_name.as_deref().unwrap_or("Unnamed product");
+                        if let (true, Some(code)) = (result_button(ui, name).clicked(), &product.code) {
+                            clicked_code = Some(code.clone());
+                        }
+                    }
+                });
+            });
+        if search {
+            self.split_details = None;
+            self.run_search(Pane::Split, SearchQuery::Terms(self.split.term.clone()), 1);
+        } else if let (Some(page), Some(query)) = (requested_page, self.split.active_query.clone()) {
+            self.run_search(Pane::Split, query, page);
+        }
+        if back {
+            self.split_details = None;
+        }
+        if let Some(code) = clicked_code {
+            self.open_split_product(code);
+        }
+        if score_help_requested {
+            self.show_score_help = true;
+        }
+    }
+
+    fn render_details(&mut self, ctx: &egui::Context, ui: &mut egui::Ui) {
+        if self.loading == LoadingState::Details {
+            ui.horizontal(|ui| {
+                ui.spinner();
+                ui.label("Loading...");
+            });
+            if ui.button("Back").clicked() {
+                self.close_product();
+            }
+            return;
+        }
+        let mut nutrition_copied = false;
+        let mut ingredients_copied = false;
+        let mut score_help_requested = false;
+        let mut image_to_fetch = None;
+        let mut image_to_save = None;
+        let mut refresh_requested = false;
+        if let Some(product) = &self.selected_product {
+            ui.add(egui::Label::new(egui::RichText::new(product.product_name.as_deref().unwrap_or("Unnamed product")).heading()).wrap());
+            if let Some(url) = &product.image_front_url {
+                if !self.images.contains_key(url) {
+                    image_to_fetch = Some(url.clone());
+                }
+                if self.render_image(ui, url) {
+                    image_to_save = Some(url.clone());
+                }
+            }
+            let watched = api::watched_allergens(&product.allergens_tags, &self.allergen_watchlist);
+            if !watched.is_empty() {
+                egui::Frame::new()
+                    .fill(egui::Color32::from_rgb(0xb7, 0x1c, 0x1c))
+                    .corner_radius(4.0)
+                    .inner_margin(egui::Margin::same(8))
+                    .show(ui, |ui| {
+                        ui.label(
+                            egui::RichText::new(format!("⚠ Contains watched allergen: {}", watched.join(", ")))
+                                .heading()
+                                .color(egui::Color32::WHITE),
+                        );
+                    });
+            }
+            if self.render_score_header(ui, product) {
+                score_help_requested = true;
+            }
+            render_facts(ui, product);
+            ui.horizontal(|ui| {
+                ui.strong("Ingredients:");
+                if let Some(ingredients) = &product.ingredients_text {
+                    if ui.button("📋 Copy").on_hover_text("Copy the ingredients as plain text").clicked() {
+                        ui.ctx().copy_text(api::clean_ingredients(ingredients));
+                        ingredients_copied = true;
+                    }
+                }
+            });
+            ui.add(egui::Label::new(product.ingredients_text.as_deref().unwrap_or("N/A")).selectable(true).wrap());
+            if product.ingredients_incomplete() {
+                ui.weak("ℹ Ingredient data incomplete")
+                    .on_hover_text("The ingredients have not been fully analysed yet, so allergens may be missing");
+            }
+            render_chips(ui, "Labels", &product.labels_tags);
+            if let Some(serving_size) = &product.serving_size {
+                match nutrition::parse_serving_size(serving_size) {
+                    Some((amount, unit)) => ui.label(format!(
+                        "Serving size: {} {}",
+                        format::fmt_number(amount, 1, &self.config.locale),
+                        unit
+                    )),
+                    None => ui.label(format!("Serving size: {} (values shown per 100g)", serving_size)),
+                };
+            }
+            if render_nutrition(
+                ui,
+                product,
+                &mut self.nutrition_basis,
+                &mut self.show_daily_values,
+                &self.config.locale,
+            ) {
+                nutrition_copied = true;
+            }
+            self.render_packaging(ui, product);
+            if let Some(timestamp) = product.last_modified_t {
+                ui.label(format!("Last modified: {}", format::fmt_date(timestamp, &self.config.locale)));
+            }
+            let refresh = self.refreshes.entry(product.code.clone()).or_default();
+            ui.horizontal(|ui| {
+                let button = ui.add_enabled(!refresh.in_flight, egui::Button::new("⟳ Refresh"));
+                if button.on_hover_text("Fetch the latest data, bypassing the cache").clicked() {
+                    refresh_requested = true;
+                }
+                if ui.checkbox(&mut refresh.auto, "Auto-refresh every").changed() && refresh.last_refresh.is_none() {
+                    // Count the interval from now rather than refreshing at once
+                    refresh.last_refresh = Some(Instant::now());
+                }
+                ui.add(egui::DragValue::new(&mut refresh.interval_minutes).range(1..=120).suffix(" min"));
+                if refresh.in_flight {
+                    ui.spinner();
+                } else if let Some(last) = refresh.last_refresh {
+                    ui.weak(format!("Refreshed {} min ago", last.elapsed().as_secs() / 60));
+                    // Keep the age current
+                    ui.ctx().request_repaint_after(Duration::from_secs(60));
+                }
+            });
+        }
+        if nutrition_copied {
+            self.notify("Nutrition table copied");
+        }
+        if ingredients_copied {
+            self.notify("Ingredients copied");
+        }
+        if score_help_requested {
+            self.show_score_help = true;
+        }
+        if let Some(url) = image_to_fetch {
+            self.fetch_image(ctx, url);
+        }
+        if let Some(url) = image_to_save {
+            self.save_image(&url);
+        }
+        if let (true, Some(product)) = (refresh_requested, &self.selected_product) {
+            self.refresh_product(product.code.clone());
+        }
+        let mut similar_query = None;
+        let mut link_copied = false;
+        let mut favorite_toggled = None;
+        ui.horizontal(|ui| {
+            if ui.button("Back").clicked() {
+                self.close_product();
+            }
+            if let Some(product) = &self.selected_product {
+                let favorite = self.favorites.iter().any(|favorite| favorite.code == product.code);
+                let (star, hint) = if favorite {
+                    ("★", "Remove from favorites")
+                } else {
+                    ("☆", "Add to favorites")
+                };
+                let star = ui.button(format!("{} Favorite", star)).on_hover_text(hint);
+                star.widget_info(|| egui::WidgetInfo::labeled(egui::WidgetType::Button, true, hint));
+                if star.clicked() {
+                    favorite_toggled = Some(product.summary());
+                }
+                if ui
+                    .button("✏ Report data issue")
+                    .on_hover_text("Fix this product on the OpenFoodFacts website")
+                    .clicked()
+                {
+                    ctx.open_url(egui::OpenUrl::new_tab(api::edit_product_url(&self.config, &product.code)));
+                }
+                if ui
+                    .button("🔗 Copy share link")
+                    .on_hover_text(format!(
+                        "Opens this product when passed to the app, or run it with --barcode {}",
+                        product.code
+                    ))
+                    .clicked()
+                {
+                    ctx.copy_text(links::product_link(&product.code));
+                    link_copied = true;
+                }
+                let similar = product.similar_query();
+                let response = ui
+                    .add_enabled(similar.is_some(), egui::Button::new("🔍 Find similar"))
+                    .on_hover_text("Search products from the same category or brand")
+                    .on_disabled_hover_text("This product has no category or brand to search by");
+                if let (true, Some(query)) = (response.clicked(), similar) {
+                    similar_query = Some(query);
+                }
+            }
+        });
+        if link_copied {
+            self.notify("Share link copied");
+        }
+        if let Some(product) = favorite_toggled {
+            self.toggle_favorite(product);
+        }
+        if let Some(query) = similar_query {
+            self.start_search(query);
+        }
+    }
+
+    fn render_comparison(&mut self, ui: &mut egui::Ui) {
+        ui.horizontal(|ui| {
+            if ui.button("Back").clicked() {
+                self.close_comparison();
+            }
+            ui.heading("Comparison");
+        });
+        if self.comparison.is_empty() {
+            return;
+        }
+        let mut score_help_requested = false;
+        egui::ScrollArea::both().show(ui, |ui| {
+            ui.columns(self.comparison.len(), |columns| {
+                for (column, (code, slot)) in columns.iter_mut().zip(&self.comparison) {
+                    match slot {
+                        ProductSlot::Loading => {
+                            column.horizontal(|ui| {
+                                ui.spinner();
+                                ui.label(format!("Loading {}...", code));
+                            });
+                        }
+                        ProductSlot::Failed(err) => {
+                            column.label(format!("{}: {}", code, err));
+                        }
+                        ProductSlot::Loaded(product) => {
+                            column.strong(product.product_name.as_deref().unwrap_or("Unnamed product"));
+                            if self.render_score_header(column, product) {
+                                score_help_requested = true;
+                            }
+                            let nutriments = &product.nutriments;
+                            for (label, value) in [
+                                ("Energy (kcal)", nutriments.energy_kcal_100g),
+                                ("Fat", nutriments.fat_100g),
+                                ("Sugars", nutriments.sugars_100g),
+                                ("Salt", nutriments.salt_100g),
+                            ] {
+                                let value = value.map_or("N/A".to_string(), |value| format::fmt_number(value, 1, &self.config.locale));
+                                column.label(format!("{}: {}", label, value));
+                            }
+                        }
+                    }
+                }
+            });
+        });
+        if score_help_requested {
+            self.show_score_help = true;
+        }
+    }
+
+    /// Renders the Nutri-Score, NOVA and Eco-Score of `product` as one row of badges.
+    /// Returns whether the user asked what the scores mean.
+    fn render_score_header(&self, ui: &mut egui::Ui, product: &ProductDetails) -> bool {
+        ui.horizontal(|ui| {
+            let nutriscore = product.nutriscore();
+            score_badge(
+                ui,
+                NUTRISCORE.name,
+                nutriscore.map(|grade| grade.to_ascii_uppercase().to_string()),
+                nutriscore,
+                NUTRISCORE.summary,
+            );
+            let nova = product.nova();
+            score_badge(
+                ui,
+                NOVA.name,
+                nova.map(|group| group.to_string()),
+                nova.map(|group| NOVA.legend[usize::from(group) - 1].1),
+                NOVA.summary,
+            );
+            let ecoscore = product.ecoscore();
+            score_badge(
+                ui,
+                ECOSCORE.name,
+                ecoscore.map(|grade| grade.to_ascii_uppercase().to_string()),
+                ecoscore,
+                ECOSCORE.summary,
+            );
+            ui.link("ⓘ What do these mean?").clicked()
+        })
+        .inner
+    }
+
+    fn render_score_help(&mut self, ctx: &egui::Context) {
+        if !self.show_score_help {
+            return;
+        }
+        let modal = egui::Modal::new(egui::Id::new("score_help")).show(ctx, |ui| {
+            ui.set_max_width(420.0);
+            for system in [NUTRISCORE, NOVA, ECOSCORE] {
+                ui.heading(system.name);
+                ui.label(system.summary);
+                ui.add(egui::Label::new(egui::RichText::new(system.explanation).weak()).wrap());
+                ui.horizontal(|ui| {
+                    for (value, grade) in system.legend {
+                        score_badge(ui, "", Some(value.to_string()), Some(*grade), system.summary);
+                    }
+                });
+                ui.add_space(8.0);
+            }
+            ui.button("Close").clicked()
+        });
+        if modal.inner || modal.should_close() {
+            self.show_score_help = false;
+        }
+    }
+
+    /// Renders the packaging components, or the packaging tags when no structured data exist.
+    fn render_packaging(&self, ui: &mut egui::Ui, product: &ProductDetails) {
+        if !product.packagings.is_empty() {
+            ui.label("Packaging:");
+            egui::Grid::new("packaging_grid").striped(true).show(ui, |ui| {
+                ui.strong("Shape");
+                ui.strong("Material");
+                ui.strong("Recycling");
+                ui.end_row();
+                for packaging in &product.packagings {
+                    for field in [&packaging.shape, &packaging.material, &packaging.recycling] {
+                        ui.label(field.as_deref().map_or("N/A".to_string(), api::tag_label));
+                    }
+                    ui.end_row();
+                }
+            });
+        } else if !product.packaging_tags.is_empty() {
+            let tags: Vec<String> = product.packaging_tags.iter().map(|tag| api::tag_label(tag)).collect();
+            ui.add(egui::Label::new(format!("Packaging: {}", tags.join(" — "))).wrap());
+        }
+    }
+
+    /// Asks the user to confirm `action` before running it.
+    fn confirm(&mut self, action: ConfirmAction) {
+        self.pending_confirm = Some(action);
+    }
+
+    fn run_confirmed(&mut self, action: ConfirmAction) {
+        match action {
+            ConfirmAction::ClearHistory => self.history.clear(),
+        }
+    }
+
+    fn render_confirm(&mut self, ctx: &egui::Context) {
+        let Some(action) = self.pending_confirm else {
+            return;
+        };
+        let mut confirmed = None;
+        egui::Window::new("Are you sure?")
+            .collapsible(false)
+            .resizable(false)
+            .anchor(egui::Align2::CENTER_CENTER, egui::Vec2::ZERO)
+            .show(ctx, |ui| {
+                ui.label(action.prompt());
+                ui.horizontal(|ui| {
+                    if ui.button("Cancel").clicked() {
+                        confirmed = Some(false);
+                    }
+                    if ui.button("Confirm").clicked() {
+                        confirmed = Some(true);
+                    }
+                });
+            });
+        if let Some(confirmed) = confirmed {
+            self.pending_confirm = None;
+            if confirmed {
+                self.run_confirmed(action);
+            }
+        }
+    }
+
+    fn render_shortcuts(&mut self, ctx: &egui::Context) {
+        egui::Window::new("Keyboard shortcuts")
+            .open(&mut self.show_shortcuts)
+            .collapsible(false)
+            .resizable(false)
+            .show(ctx, |ui| {
+                egui::Grid::new("shortcuts_grid").show(ui, |ui| {
+                    for (shortcut, description) in SHORTCUTS {
+                        ui.strong(ctx.format_shortcut(shortcut));
+                        ui.label(*description);
+                        ui.end_row();
+                    }
+                });
+            });
+    }
+
+    fn render_settings(&mut self, ctx: &egui::Context) {
+        let mut open = self.show_settings;
+        egui::Window::new("Settings")
+            .open(&mut open)
+            .collapsible(false)
+            .show(ctx, |ui| {
+                ui.heading("Cache");
+                ui.horizontal(|ui| {
+                    ui.label("Keep fetched products for");
+                    ui.add(egui::DragValue::new(&mut self.cache_ttl_hours).range(1..=720).suffix(" h"));
+                });
+                ui.label(format!("{} products cached", self.details_cache.len()));
+                ui.separator();
+                ui.heading("Language");
+                ui.horizontal(|ui| {
+                    let label = ui.label("Product data, numbers and dates in");
+                    let selected = LOCALES
+                        .iter()
+                        .find(|(code, _)| *code == self.config.locale)
+                        .map_or(self.config.locale.as_str(), |(_, name)| name);
+                    egui::ComboBox::from_id_salt("locale")
+                        .selected_text(selected)
+                        .show_ui(ui, |ui| {
+                            for (code, name) in LOCALES {
+                                ui.selectable_value(&mut self.config.locale, code.to_string(), name);
+                            }
+                        })
+                        .response
+                        .labelled_by(label.id);
+                });
+                ui.separator();
+                ui.heading("Network");
+                ui.horizontal(|ui| {
+                    ui.label("Attempts per request");
+                    ui.add(egui::DragValue::new(&mut self.config.retry_attempts).range(1..=MAX_RETRY_ATTEMPTS))
+                        .on_hover_text("Requests failing with a network error or timeout are retried");
+                });
+                ui.separator();
+                ui.heading("Login");
+                if ui
+                    .checkbox(&mut self.use_staging, "Use the staging server")
+                    .on_hover_text(format!("Query {} instead of the production server", self.flavor.staging_url()))
+                    .changed()
+                {
+                    self.update_base_url();
+                }
+                ui.checkbox(&mut self.config.always_authenticate, "Also log in to the production server");
+                egui::Grid::new("login_grid").show(ui, |ui| {
+                    let label = ui.label("Username");
+                    ui.text_edit_singleline(&mut self.auth_username).labelled_by(label.id);
+                    ui.end_row();
+                    let label = ui.label("Password");
+                    ui.add(egui::TextEdit::singleline(&mut self.auth_password).password(true)).labelled_by(label.id);
+                    ui.end_row();
+                });
+                ui.horizontal(|ui| {
+                    if ui.button("Save login").on_hover_text("The password is kept in the system keyring").clicked() {
+                        self.save_login();
+                    }
+                    if ui.add_enabled(self.config.credentials.is_some(), egui::Button::new("Forget login")).clicked() {
+                        self.forget_login();
+                    }
+                });
+                ui.separator();
+                ui.heading("Allergen watchlist");
+                ui.label("Products containing these allergens are flagged.");
+                let mut removed = None;
+                for (index, allergen) in self.allergen_watchlist.iter().enumerate() {
+                    ui.horizontal(|ui| {
+                        ui.label(allergen);
+                        let remove = ui.button("🗑").on_hover_text("Remove");
+                        remove.widget_info(|| egui::WidgetInfo::labeled(egui::WidgetType::Button, true, format!("Remove {}", allergen)));
+                        if remove.clicked() {
+                            removed = Some(index);
+                        }
+                    });
+                }
+                if let Some(index) = removed {
+                    self.allergen_watchlist.remove(index);
+                }
+                ui.horizontal(|ui| {
+                    let response = ui.add(egui::TextEdit::singleline(&mut self.new_allergen).hint_text("e.g. nuts"));
+                    let submitted = response.lost_focus() && ui.input(|i| i.key_pressed(egui::Key::Enter));
+                    if ui.button("Add").clicked() || submitted {
+                        let allergen = self.new_allergen.trim().to_lowercase();
+                        if !allergen.is_empty() && !self.allergen_watchlist.contains(&allergen) {
+                            self.allergen_watchlist.push(allergen);
+                        }
+                        self.new_allergen.clear();
+                    }
+                });
+            });
+        self.show_settings = open;
+    }
+
+    /// Points the requests at the production or staging server of [`Self::flavor`].
+    fn update_base_url(&mut self) {
+        self.config.base_url = if self.use_staging {
+            self.flavor.staging_url()
+        } else {
+            self.flavor.base_url().to_string()
+        };
+    }
+
+    /// Stores the login of the settings in the keyring and starts sending it.
+    fn save_login(&mut self) {
+        self.auth_username = self.auth_username.trim().to_string();
+        if self.auth_username.is_empty() {
+            self.notify("Enter a username first");
+            return;
+        }
+        let login = api::Credentials {
+            username: self.auth_username.clone(),
+            password: self.auth_password.clone(),
+        };
+        match credentials::store(&login) {
+            Ok(()) => self.notify("Login saved"),
+            Err(e) => {
+                error!("Failed to save the login: {}", e);
+                self.notify(format!("Could not save the login: {}", e));
+            }
+        }
+        // Still usable for this session when the keyring is unavailable
+        self.config.credentials = Some(login);
+    }
+
+    /// Removes the login from the keyring and stops sending it.
+    fn forget_login(&mut self) {
+        if let Some(login) = self.config.credentials.take() {
+            if let Err(e) = credentials::forget(&login.username) {
+                error!("Failed to forget the login: {}", e);
+                self.notify(format!("Could not remove the login from the keyring: {}", e));
+            }
+        }
+        self.auth_password.clear();
+    }
+
+    fn render_menu_bar(&mut self, ui: &mut egui::Ui) {
+        egui::menu::bar(ui, |ui| {
+            ui.menu_button("File", |ui| {
+                let has_results = !self.search.results.is_empty();
+                if ui.add_enabled(has_results, egui::Button::new("Export results as CSV…")).clicked() {
+                    self.export_results(ExportFormat::Csv);
+                    ui.close_menu();
+                }
+                if ui.add_enabled(has_results, egui::Button::new("Export results as JSON…")).clicked() {
+                    self.export_results(ExportFormat::Json);
+                    ui.close_menu();
+                }
+                ui.separator();
+                if ui.add_enabled(!self.favorites.is_empty(), egui::Button::new("Export favorites…")).clicked() {
+                    self.export_favorites();
+                    ui.close_menu();
+                }
+                if ui.button("Import favorites…").clicked() {
+                    self.import_favorites();
+                    ui.close_menu();
+                }
+                ui.separator();
+                if ui.button("Settings").clicked() {
+                    self.show_settings = true;
+                    ui.close_menu();
+                }
+                ui.separator();
+                if ui.button("Quit").clicked() {
+                    ui.ctx().send_viewport_cmd(egui::ViewportCommand::Close);
+                }
+            });
+            ui.menu_button("View", |ui| {
+                ui.label("Theme");
+                egui::widgets::global_theme_preference_buttons(ui);
+                ui.separator();
+                ui.radio_value(&mut self.result_layout, ResultLayout::List, "Results as a list");
+                ui.radio_value(&mut self.result_layout, ResultLayout::Grid, "Results as a grid");
+                ui.separator();
+                ui.checkbox(&mut self.split_view, "Split view")
+                    .on_hover_text("Run a second, independent search side by side");
+                if ui.button("Favorites").clicked() {
+                    self.open_favorites();
+                    ui.close_menu();
+                }
+                ui.checkbox(&mut self.show_history, "History");
+                ui.checkbox(&mut self.show_request_log, "Request log");
+            });
+            ui.menu_button("Help", |ui| {
+                if ui.button("Keyboard shortcuts").clicked() {
+                    self.show_shortcuts = true;
+                    ui.close_menu();
+                }
+                if ui.button("What do the scores mean?").clicked() {
+                    self.show_score_help = true;
+                    ui.close_menu();
+                }
+                ui.separator();
+                if ui.button("About").clicked() {
+                    self.show_about = true;
+                    ui.close_menu();
+                }
+            });
+        });
+    }
+
+    /// Asks where to save the current page of results, and writes it there in `export_format`.
+    fn export_results(&mut self, export_format: ExportFormat) {
+        let (extension, contents) = match export_format {
+            ExportFormat::Csv => ("csv", export::results_csv(&self.search.results)),
+            ExportFormat::Json => ("json", export::results_json(&self.search.results)),
+        };
+        let Some(path) = rfd::FileDialog::new()
+            .add_filter(extension.to_uppercase(), &[extension])
+            .set_file_name(format!("results.{}", extension))
+            .save_file()
+        else {
+            return;
+        };
+        match std::fs::write(&path, contents) {
+            Ok(()) => self.notify(format!("Exported {} products", self.search.results.len())),
+            Err(e) => {
+                error!("Failed to export results to {}: {}", path.display(), e);
+                self.notify(format!("Could not export results: {}", e));
+            }
+        }
+    }
+
+    /// Renders the table of recent downloads, newest first.
+    fn render_request_log(&mut self, ctx: &egui::Context) {
+        let mut open = self.show_request_log;
+        let mut clear = false;
+        egui::Window::new("Request log")
+            .open(&mut open)
+            .default_width(640.0)
+            .show(ctx, |ui| {
+                ui.horizontal(|ui| {
+                    ui.label(format!("Last {} requests", self.request_log.len()));
+                    clear = ui.button("Clear").clicked();
+                });
+                let row_height = ui.spacing().interact_size.y;
+                egui_extras::TableBuilder::new(ui)
+                    .striped(true)
+                    .column(egui_extras::Column::auto())
+                    .column(egui_extras::Column::auto())
+                    .column(egui_extras::Column::auto())
+                    .column(egui_extras::Column::auto())
+                    .column(egui_extras::Column::remainder().clip(true))
+                    .header(row_height, |mut header| {
+                        for title in ["Age", "Status", "Duration", "Size", "URL"] {
+                            header.col(|ui| {
+                                ui.strong(title);
+                            });
+                        }
+                    })
+                    .body(|body| {
+                        body.rows(row_height, self.request_log.len(), |mut row| {
+                            let (finished_at, record) = &self.request_log[self.request_log.len() - 1 - row.index()];
+                            row.col(|ui| {
+                                ui.label(format!("{}s", finished_at.elapsed().as_secs()));
+                            });
+                            row.col(|ui| match (record.status, &record.error) {
+                                (Some(status), _) => {
+                                    ui.label(status.to_string());
+                                }
+                                (None, Some(error)) => {
+                                    ui.label("—").on_hover_text(error);
+                                }
+                                (None, None) => {
+                                    ui.label("—");
+                                }
+                            });
+                            row.col(|ui| {
+                                ui.label(format!("{} ms", record.duration.as_millis()));
+                            });
+                            row.col(|ui| {
+                                ui.label(record.bytes.map_or_else(|| "—".to_string(), format::fmt_bytes));
+                            });
+                            row.col(|ui| {
+                                ui.label(&record.url).on_hover_text(&record.url);
+                            });
+                        });
+                    });
+            });
+        if clear {
+            self.request_log.clear();
+        }
+        self.show_request_log = open;
+    }
+
+    fn render_about(&mut self, ctx: &egui::Context) {
+        egui::Window::new("About")
+            .open(&mut self.show_about)
+            .collapsible(false)
+            .resizable(false)
+            .show(ctx, |ui| {
+                ui.heading(APP_NAME);
+                ui.label(format!("Version {}", env!("CARGO_PKG_VERSION")));
+                ui.hyperlink_to("Source code and issues", env!("CARGO_PKG_REPOSITORY"));
+            });
+    }
+
+    /// Shows `text` in a toast for a few seconds.
+    fn notify(&mut self, text: impl Into<String>) {
+        self.toasts.push(Toast {
+            text: text.into(),
+            shown_at: Instant::now(),
+        });
+    }
+
+    fn render_toasts(&mut self, ctx: &egui::Context) {
+        self.toasts.retain(|toast| toast.shown_at.elapsed() < TOAST_DURATION);
+        if self.toasts.is_empty() {
+            return;
+        }
+        egui::Area::new(egui::Id::new("toasts"))
+            .anchor(egui::Align2::RIGHT_BOTTOM, egui::vec2(-10.0, -10.0))
+            .show(ctx, |ui| {
+                for toast in &self.toasts {
+                    egui::Frame::popup(ui.style()).show(ui, |ui| {
+                        ui.label(&toast.text);
+                    });
+                }
+            });
+        ctx.request_repaint_after(Duration::from_millis(250));
+    }
+
+    /// Applies the answer of a background request to the state.
+    fn handle_message(&mut self, message: Message) {
+        match message {
+            Message::SearchResults(pane, request_id, response) => {
+                let state = self.search_state(pane);
+                if request_id != state.request_id {
+                    debug!("Dropping results of abandoned search {}", request_id);
+                    return;
+                }
+                state.finish(response);
+            }
+            Message::ProductDetails(generation, details) => {
+                if generation != self.details_generation {
+                    debug!("Dropping stale details for {}", details.code);
+                    return;
+                }
+                if let Some(LastRequest::Details(requested)) = &self.last_request {
+                    if *requested != details.code {
+                        let text = format!("No product {}, showing {} instead", requested, details.code);
+                        self.notify(text);
+                    }
+                }
+                self.record_history(details.summary());
+                self.details_cache.insert(&self.cache_source(), (*details).clone(), cache::now());
+                self.selected_product = Some(*details);
+                self.loading = LoadingState::None;
+            }
+            Message::SearchError(pane, request_id, _) if request_id != self.search_state(pane).request_id => {
+                debug!("Dropping error of abandoned search {}", request_id);
+            }
+            Message::SearchError(Pane::Main, _, err) if self.search.loading_more && err != AppError::Offline => {
+                // Keep the pages already shown
+                self.search.fail();
+                self.notify(format!("Could not load more results: {}", err));
+            }
+            Message::SearchError(Pane::Main, _, err) => {
+                self.search.fail();
+                self.show_error(err);
+            }
+            Message::SearchError(Pane::Split, _, err) => {
+                self.split.fail();
+                if err == AppError::Offline {
+                    self.show_error(err);
+                } else {
+                    self.notify(format!("Search failed: {}", err));
+                }
+            }
+            Message::SplitProduct(code, result) => {
+                if let Ok(details) = &result {
+                    self.details_cache.insert(&self.cache_source(), (**details).clone(), cache::now());
+                }
+                if let Some((split_code, slot)) = &mut self.split_details {
+                    if *split_code == code {
+                        *slot = match result {
+                            Ok(product) => ProductSlot::Loaded(product),
+                            Err(err) => ProductSlot::Failed(err),
+                        };
+                    }
+                }
+            }
+            Message::DetailsError(generation, err) => {
+                if generation == self.details_generation {
+                    self.show_error(err);
+                }
+            }
+            Message::Toast(text) => self.notify(text),
+            Message::ComparedProduct(generation, code, result) => {
+                if generation != self.comparison_generation {
+                    return;
+                }
+                if matches!(result, Err(AppError::Offline)) {
+                    self.show_error(AppError::Offline);
+                }
+                let slot = match result {
+                    Ok(product) => ProductSlot::Loaded(product),
+                    Err(err) => ProductSlot::Failed(err),
+                };
+                if let Some((_, column)) = self.comparison.iter_mut().find(|(column_code, _)| *column_code == code) {
+                    *column = slot;
+                }
+            }
+            Message::RefreshedProduct(code, result) => {
+                let state = self.refreshes.entry(code.clone()).or_default();
+                state.in_flight = false;
+                state.last_refresh = Some(Instant::now());
+                match result {
+                    Ok(details) => {
+                        self.details_cache.insert(&self.cache_source(), (*details).clone(), cache::now());
+                        if let Some(product) = self.selected_product.as_mut().filter(|product| product.code == code) {
+                            *product = (*details).clone();
+                        }
+                        if let Some((_, tab)) = self.tabs.iter_mut().find(|(tab_code, _)| *tab_code == code) {
+                            *tab = ProductSlot::Loaded(details);
+                        }
+                    }
+                    Err(err) => self.notify(format!("Could not refresh {}: {}", code, err)),
+                }
+            }
+            Message::TabProduct(code, result) => {
+                if let Ok(details) = &result {
+                    self.details_cache.insert(&self.cache_source(), (**details).clone(), cache::now());
+                }
+                let slot = match result {
+                    Ok(product) => ProductSlot::Loaded(product),
+                    Err(err) => ProductSlot::Failed(err),
+                };
+                if let Some((_, tab)) = self.tabs.iter_mut().find(|(tab_code, _)| *tab_code == code) {
+                    *tab = slot;
+                }
+            }
+            Message::ImageProgress { url, fraction } => {
+                if let Some(image @ ImageState::Loading(_)) = self.images.get_mut(&url) {
+                    *image = ImageState::Loading(fraction);
+                }
+            }
+            Message::Image { url, result } => match result {
+                Ok(bytes) => {
+                    self.images.insert(url, ImageState::Loaded(bytes.into()));
+                }
+                Err(err) => {
+                    self.images.insert(url, ImageState::Failed(err));
+                }
+            },
+            Message::Connectivity(reachable) => {
+                self.last_connectivity_check = Some(Instant::now());
+                if reachable && self.offline {
+                    self.offline = false;
+                    self.notify("Back online");
+                }
+            }
+            Message::RequestLogged(record) => {
+                if self.request_log.len() == REQUEST_LOG_LIMIT {
+                    self.request_log.pop_front();
+                }
+                self.request_log.push_back((Instant::now(), record));
+            }
+        }
+    }
+}
+
+impl eframe::App for OpenFoodFactsViewer {
+    fn save(&mut self, storage: &mut dyn eframe::Storage) {
+        eframe::set_value(storage, HISTORY_KEY, &self.history);
+        eframe::set_value(storage, FAVORITES_KEY, &self.favorites);
+        eframe::set_value(storage, SEARCH_HISTORY_KEY, &self.search_history);
+        eframe::set_value(storage, SEARCH_TERM_KEY, &self.search.term);
+        eframe::set_value(storage, PAGE_SIZE_KEY, &self.config.page_size);
+        eframe::set_value(storage, RETRY_ATTEMPTS_KEY, &self.config.retry_attempts);
+        eframe::set_value(storage, LOCALE_KEY, &self.config.locale);
+        eframe::set_value(storage, USE_STAGING_KEY, &self.use_staging);
+        eframe::set_value(storage, ALWAYS_AUTHENTICATE_KEY, &self.config.always_authenticate);
+        eframe::set_value(storage, AUTH_USERNAME_KEY, &self.auth_username);
+        eframe::set_value(storage, ALLERGEN_WATCHLIST_KEY, &self.allergen_watchlist);
+        eframe::set_value(storage, CACHE_TTL_HOURS_KEY, &self.cache_ttl_hours);
+    }
+
+    fn on_exit(&mut self, _gl: Option<&eframe::glow::Context>) {
+        // Let the worker threads give up instead of finishing requests nobody will see
+        for cancel in [
+            &self.search.cancel,
+            &self.split.cancel,
+            &self.details_cancel,
+            &self.comparison_cancel,
+            &self.background_cancel,
+        ] {
+            cancel.store(true, Ordering::Relaxed);
+        }
+        if let Some(path) = &self.cache_path {
+            let ttl = self.cache_ttl();
+            if let Err(e) = self.details_cache.save(path, ttl, cache::now()) {
+                error!("Failed to save the details cache to {}: {}", path.display(), e);
+            }
+        }
+    }
+
+    fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
+        // The search box starts focused, so keyboard users begin at the top of the focus order
+        let focus_search = ctx.cumulative_pass_nr() == 0
+            || ctx.input_mut(|i| i.consume_shortcut(&FOCUS_SEARCH) || i.consume_shortcut(&FOCUS_SEARCH_MAC));
+        if ctx.input_mut(|i| i.consume_shortcut(&TOGGLE_SHORTCUTS)) {
+            self.show_shortcuts = !self.show_shortcuts;
+        }
+        if ctx.input_mut(|i| i.consume_shortcut(&TOGGLE_REQUEST_LOG)) {
+            self.show_request_log = !self.show_request_log;
+        }
+
+        // Top panel with search bar
+        egui::TopBottomPanel::top("top_panel").show(ctx, |ui| {
+            self.render_menu_bar(ui);
+            ui.horizontal(|ui| {
+                let previous_flavor = self.flavor;
+                egui::ComboBox::from_id_salt("flavor")
+                    .selected_text(self.flavor.label())
+                    .show_ui(ui, |ui| {
+                        for flavor in Flavor::ALL {
+                            ui.selectable_value(&mut self.flavor, flavor, flavor.label());
+                        }
+                    });
+                if self.flavor != previous_flavor {
+                    self.update_base_url();
+                }
+                let label = ui.label("Look up:");
+                ui.radio_value(&mut self.search_mode, SearchMode::Terms, "Search");
+                ui.radio_value(&mut self.search_mode, SearchMode::Barcode, "Barcode")
+                    .on_hover_text("Open the product with this EAN/UPC code");
+                let hint = match self.search_mode {
+                    SearchMode::Terms => "Product name or keywords",
+                    SearchMode::Barcode => "e.g. 3017620422003",
+                };
+                let output = egui::TextEdit::singleline(&mut self.search.term).hint_text(hint).show(ui);
+                let response = output.response.labelled_by(label.id);
+                if response.changed() && self.search_mode == SearchMode::Terms {
+                    self.search_edited = Some(Instant::now());
+                }
+                if focus_search {
+                    // Select the previous term so typing replaces it
+                    let mut state = output.state;
+                    state.cursor.set_char_range(Some(egui::text::CCursorRange::two(
+                        egui::text::CCursor::new(0),
+                        egui::text::CCursor::new(self.search.term.chars().count()),
+                    )));
+                    state.store(ui.ctx(), response.id);
+                    response.request_focus();
+                }
+                let barcode = match self.search_mode {
+                    SearchMode::Terms => None,
+                    SearchMode::Barcode => Some(self.barcode_input()),
+                };
+                let invalid_barcode = matches!(barcode, Some(None));
+                let submit_label = match self.search_mode {
+                    SearchMode::Terms => "Search",
+                    SearchMode::Barcode => "Open",
+                };
+                let submitted = ui.add_enabled(!invalid_barcode, egui::Button::new(submit_label)).clicked()
+                    || (response.lost_focus() && ui.input(|i: &egui::InputState| i.key_pressed(egui::Key::Enter)));
+                match &barcode {
+                    Some(Some(code)) if *code != self.search.term.trim() => {
+                        ui.weak(format!("Barcode {}", code));
+                    }
+                    Some(None) if !self.search.term.trim().is_empty() => {
+                        ui.colored_label(ui.visuals().error_fg_color, "No barcode found");
+                    }
+                    _ => {}
+                }
+                if submitted && !invalid_barcode {
+                    match self.search_mode {
+                        SearchMode::Terms => self.submit_search(),
+                        SearchMode::Barcode => self.submit_barcode(),
+                    }
+                }
+                let mut recent = None;
+                ui.add_enabled_ui(!self.search_history.is_empty(), |ui| {
+                    egui::ComboBox::from_id_salt("recent_searches")
+                        .selected_text("Recent")
+                        .show_ui(ui, |ui| {
+                            for term in &self.search_history {
+                                if ui.selectable_label(false, term).clicked() {
+                                    recent = Some(term.clone());
+                                }
+                            }
+                        })
+                        .response
+                        .on_hover_text("Search again for a recent term");
+                });
+                if let Some(term) = recent {
+                    self.search_mode = SearchMode::Terms;
+                    self.search.term = term;
+                    self.submit_search();
+                }
+                let mut browsed = None;
+                egui::ComboBox::from_id_salt("browse_category")
+                    .selected_text("Browse")
+                    .show_ui(ui, |ui| {
+                        for (tag, label) in api::BROWSE_CATEGORIES {
+                            if ui.selectable_label(false, label).clicked() {
+                                browsed = Some(tag);
+                            }
+                        }
+                    })
+                    .response
+                    .on_hover_text("Top products of a category");
+                if let Some(tag) = browsed {
+                    self.start_search(SearchQuery::Category(tag.to_string()));
+                }
+                if ui.selectable_label(matches!(self.view, View::Favorites), "★ Favorites").clicked() {
+                    self.open_favorites();
+                }
+                let previous_page_size = self.config.page_size;
+                egui::ComboBox::from_id_salt("page_size")
+                    .selected_text(format!("{} per page", self.config.page_size))
+                    .show_ui(ui, |ui| {
+                        for page_size in PAGE_SIZES {
+                            ui.selectable_value(&mut self.config.page_size, page_size, page_size.to_string());
+                        }
+                    });
+                if self.config.page_size != previous_page_size {
+                    if let Some(query) = self.search.active_query.clone() {
+                        self.start_search(query);
+                    }
+                }
+            });
+        });
+
+        self.render_offline_banner(ctx);
+
+        // Attribution required by the database license
+        egui::TopBottomPanel::bottom("footer").show(ctx, |ui| {
+            ui.horizontal(|ui| {
+                ui.spacing_mut().item_spacing.x = 0.0;
+                ui.small("Data © ");
+                ui.hyperlink_to(egui::RichText::new(format!("{} contributors", self.flavor.label())).small(), &self.config.base_url);
+                ui.small(", ");
+                ui.hyperlink_to(egui::RichText::new("ODbL").small(), ODBL_URL);
+            });
+        });
+
+        self.render_history(ctx);
+        self.render_split_panel(ctx);
+
+        // Central panel for content
+        egui::CentralPanel::default().show(ctx, |ui| {
+            self.render_tabs(ui);
+            if let Some(error) = &self.error_message {
+                ui.label(format!("Error: {}", error));
+                let retryable = matches!(error, AppError::Network(_) | AppError::Timeout | AppError::Http(500..=599));
+                ui.horizontal(|ui| {
+                    if retryable && ui.button("Retry").clicked() {
+                        self.retry();
+                    }
+                    if matches!(self.view, View::ProductDetails) && ui.button("Back").clicked() {
+                        self.close_product();
+                    }
+                });
+            } else {
+                match self.view {
+                    View::SearchResults => self.render_search_results(ui),
+                    View::ProductDetails => self.render_details(ctx, ui),
+                    View::Comparison => self.render_comparison(ui),
+                    View::Favorites => self.render_favorites(ui),
+                }
+            }
+        });
+
+        self.poll_connectivity(ctx);
+        self.poll_auto_refresh(ctx);
+        self.poll_live_search(ctx);
+        self.render_shortcuts(ctx);
+        self.render_about(ctx);
+        self.render_score_help(ctx);
+        self.render_settings(ctx);
+        self.render_request_log(ctx);
+        self.render_confirm(ctx);
+        self.render_toasts(ctx);
+
+        // Handle messages from background threads
+        while let Ok(message) = self.message_receiver.try_recv() {
+            self.handle_message(message);
+        }
+    }
+}
+
+/// Reads the view to open at startup, given as `--barcode <code>`, or `--open <link>` (or just
+/// `<link>`) with a product or search share link.
+fn startup_request(mut args: impl Iterator<Item = String>) -> Option<StartupRequest> {
+    while let Some(arg) = args.next() {
+        if arg == "--barcode" {
+            return args.next().map(|arg| StartupRequest::Product(barcode::extract_code(&arg).unwrap_or(arg)));
+        }
+        let link = if arg == "--open" { args.next()? } else { arg };
+        if let Some(code) = links::parse_product_link(&link) {
+            return Some(StartupRequest::Product(code.to_string()));
+        }
+        if let Some(search) = SearchLink::parse(&link) {
+            return Some(StartupRequest::Search(search));
+        }
+    }
+    None
+}
+
+fn main() -> eframe::Result {
+    // Initialize the logger, keeping dependencies quiet unless RUST_LOG says otherwise
+    env_logger::Builder::from_env(env_logger::Env::default().default_filter_or("warn,test_egui=info")).init();
+
+    let mut config = ApiConfig::default();
+    // Follow the system's language until one is saved with the settings
+    if let Some(locale) = ["LC_ALL", "LC_MESSAGES", "LANG"]
+        .into_iter()
+        .filter_map(|name| std::env::var(name).ok())
+        .find(|value| !value.is_empty())
+        .and_then(|value| format::language_code(&value))
+    {
+        config.locale = locale;
+    }
+    let startup_request = startup_request(std::env::args().skip(1));
+    info!(
+        "Starting OpenFoodFacts Viewer {} (base URL: {}, locale: {}, timeout: {}s, page size: {})",
+        env!("CARGO_PKG_VERSION"),
+        config.base_url,
+        config.locale,
+        config.timeout.as_secs(),
+        config.page_size
+    );
 
     let options = eframe::NativeOptions::default();
     eframe::run_native(
-        "OpenFoodFacts Viewer",
+        APP_NAME,
         options,
-        Box::new(|cc| Ok(Box::new(OpenFoodFactsViewer::new(cc)))),
-    );
-}
\ No newline at end of file
+        Box::new(|cc| Ok(Box::new(OpenFoodFactsViewer::new(cc, config, startup_request)))),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn long_chip_lists_are_collapsed() {
+        assert_eq!(visible_chip_count(40, false), CHIP_LIMIT);
+        assert_eq!(visible_chip_count(40, true), 40);
+        assert_eq!(visible_chip_count(3, false), 3);
+    }
+
+    #[test]
+    fn forty_labels_stay_within_the_panel_width() {
+        let labels: Vec<String> = (0..40).map(|i| format!("en:very-long-label-number-{}", i)).collect();
+        let ctx = egui::Context::default();
+        let input = egui::RawInput {
+            screen_rect: Some(egui::Rect::from_min_size(egui::Pos2::ZERO, egui::vec2(400.0, 800.0))),
+            ..Default::default()
+        };
+        let mut used = egui::Rect::NOTHING;
+        let mut available_width = 0.0;
+        let _ = ctx.run(input, |ctx| {
+            egui::CentralPanel::default().show(ctx, |ui| {
+                available_width = ui.available_width();
+                render_chips(ui, "Labels", &labels);
+                used = ui.min_rect();
+            });
+        });
+        assert!(used.width() <= available_width, "{} > {}", used.width(), available_width);
+    }
+
+    #[test]
+    fn buttons_meet_the_minimum_hit_height() {
+        let ctx = egui::Context::default();
+        ctx.all_styles_mut(apply_accessible_spacing);
+        let mut height = 0.0;
+        let _ = ctx.run(egui::RawInput::default(), |ctx| {
+            egui::CentralPanel::default().show(ctx, |ui| {
+                height = ui.button("✖").rect.height();
+            });
+        });
+        assert!(height >= MIN_HIT_HEIGHT, "{} < {}", height, MIN_HIT_HEIGHT);
+    }
+
+    #[test]
+    fn badge_text_stays_legible_in_both_themes() {
+        for visuals in [egui::Visuals::light(), egui::Visuals::dark()] {
+            assert_eq!(grade_colors('c', &visuals).1, egui::Color32::BLACK);
+            assert_eq!(grade_colors('a', &visuals).1, egui::Color32::WHITE);
+            assert_eq!(grade_colors('e', &visuals).1, egui::Color32::WHITE);
+        }
+        assert_ne!(grade_colors('a', &egui::Visuals::light()).0, grade_colors('a', &egui::Visuals::dark()).0);
+    }
+
+    #[test]
+    fn search_states_are_independent() {
+        let response: api::SearchResponse = serde_json::from_str(
+            r#"{"count": 45, "page_size": 20, "products": [{"code": "1", "product_name": "Skyr"}]}"#,
+        )
+        .unwrap();
+        let mut main = SearchState::default();
+        let mut split = SearchState::default();
+        main.begin(SearchQuery::Terms("greek yogurt".to_string()), 1);
+        split.begin(SearchQuery::Terms("skyr".to_string()), 2);
+        split.finish(response);
+
+        assert!(main.loading);
+        assert_eq!(main.subtitle().as_deref(), Some("Results for 'greek yogurt'"));
+        assert!(!split.loading);
+        assert_eq!(split.subtitle().as_deref(), Some("Results for 'skyr' — 45 products, page 2 of 3"));
+    }
+
+    #[test]
+    fn startup_links_restore_the_view() {
+        let args = |args: &[&str]| args.iter().map(|arg| arg.to_string()).collect::<Vec<_>>().into_iter();
+        assert!(matches!(
+            startup_request(args(&["--open", "offviewer://search?q=skyr&page=2"])),
+            Some(StartupRequest::Search(SearchLink { query: SearchQuery::Terms(terms), page: 2, .. })) if terms == "skyr"
+        ));
+        assert!(matches!(
+            startup_request(args(&["offviewer://product/3017620422003"])),
+            Some(StartupRequest::Product(code)) if code == "3017620422003"
+        ));
+        assert!(matches!(
+            startup_request(args(&["--barcode", "123"])),
+            Some(StartupRequest::Product(code)) if code == "123"
+        ));
+        assert!(startup_request(args(&["--open"])).is_none());
+    }
+
+    #[test]
+    fn refreshes_are_due_once_their_interval_elapsed() {
+        let mut state = RefreshState::default();
+        assert_eq!(state.until_due(), Duration::ZERO);
+
+        state.last_refresh = Some(Instant::now());
+        assert!(state.until_due() > Duration::from_secs(4 * 60));
+
+        state.last_refresh = Instant::now().checked_sub(Duration::from_secs(6 * 60));
+        assert_eq!(state.until_due(), Duration::ZERO);
+    }
+
+    #[test]
+    fn next_pages_are_appended_without_duplicate_requests() {
+        let page = |page: u32| -> api::SearchResponse {
+            serde_json::from_str(&format!(
+                r#"{{"count": 45, "page": {}, "page_size": 20, "products": [{{"code": "{}"}}]}}"#,
+                page, page
+            ))
+            .unwrap()
+        };
+        let mut state = SearchState::default();
+        state.begin(SearchQuery::Terms("milk".to_string()), 1);
+        assert!(state.begin_next_page().is_none());
+        state.finish(page(1));
+
+        let (_, next) = state.begin_next_page().unwrap();
+        assert_eq!(next, 2);
+        assert!(state.begin_next_page().is_none(), "a next page is already in flight");
+        state.finish(page(2));
+        assert_eq!(state.results.len(), 2);
+        assert_eq!(state.subtitle().as_deref(), Some("Results for 'milk' — 45 products, pages 1–2 of 3"));
+
+        state.begin(SearchQuery::Terms("skyr".to_string()), 1);
+        state.finish(page(1));
+        assert_eq!(state.results.len(), 1);
+        assert_eq!((state.first_page, state.page), (1, 1));
+    }
+
+    #[test]
+    fn recent_searches_are_deduplicated_and_capped() {
+        let mut history = Vec::new();
+        for i in 0..SEARCH_HISTORY_LIMIT + 5 {
+            remember_search(&mut history, &format!("term {}", i));
+        }
+        remember_search(&mut history, "term 10");
+        remember_search(&mut history, "");
+        assert_eq!(history.len(), SEARCH_HISTORY_LIMIT);
+        assert_eq!(history[0], "term 10");
+        assert_eq!(history[1], format!("term {}", SEARCH_HISTORY_LIMIT + 4));
+        assert_eq!(history.iter().filter(|term| *term == "term 10").count(), 1);
+    }
+
+    /// Answers every request with the same canned body, or error.
+    struct MockClient {
+        search: Result<&'static str, AppError>,
+        product: Result<&'static str, AppError>,
+    }
+
+    impl OpenFoodFactsClient for MockClient {
+        fn search(&self, _: &ApiConfig, _: &SearchQuery, _: u32, _: Option<api::SortBy>, _: &RequestHooks) -> Result<api::SearchResponse, AppError> {
+            Ok(serde_json::from_str(self.search.clone()?)?)
+        }
+
+        fn product(&self, _: &ApiConfig, _: &str, _: &RequestHooks) -> Result<ProductDetails, AppError> {
+            Ok(serde_json::from_str(self.product.clone()?)?)
+        }
+    }
+
+    fn mock_viewer(client: MockClient) -> OpenFoodFactsViewer {
+        OpenFoodFactsViewer::with_client(None, ApiConfig::default(), Arc::new(client), None)
+    }
+
+    /// Waits for the answer of the request in flight and applies it.
+    fn handle_answer(viewer: &mut OpenFoodFactsViewer) {
+        let message = viewer.message_receiver.recv_timeout(Duration::from_secs(5)).expect("the request was answered");
+        viewer.handle_message(message);
+    }
+
+    #[test]
+    fn search_results_fill_the_results_view() {
+        let mut viewer = mock_viewer(MockClient {
+            search: Ok(r#"{"count": 2, "page_size": 20, "products": [{"code": "111", "product_name": "Whole milk"}, {"code": "222"}]}"#),
+            product: Err(AppError::NotFound),
+        });
+        viewer.start_search(SearchQuery::Terms("milk".to_string()));
+        assert!(viewer.search.loading);
+
+        handle_answer(&mut viewer);
+        assert!(!viewer.search.loading);
+        assert!(viewer.loading == LoadingState::None);
+        assert_eq!(viewer.search.results.len(), 2);
+        assert_eq!(viewer.search.results[0].product_name.as_deref(), Some("Whole milk"));
+        assert_eq!(viewer.error_message, None);
+    }
+
+    #[test]
+    fn search_errors_are_shown() {
+        let mut viewer = mock_viewer(MockClient {
+            search: Err(AppError::Http(500)),
+            product: Err(AppError::NotFound),
+        });
+        viewer.start_search(SearchQuery::Terms("milk".to_string()));
+
+        handle_answer(&mut viewer);
+        assert!(!viewer.search.loading);
+        assert!(viewer.search.results.is_empty());
+        assert_eq!(viewer.error_message, Some(AppError::Http(500)));
+    }
+
+    #[test]
+    fn looked_up_products_open_in_the_details_view() {
+        let mut viewer = mock_viewer(MockClient {
+            search: Err(AppError::Http(500)),
+            product: Ok(r#"{"code": "3017620422003", "product_name": "Nutella"}"#),
+        });
+        viewer.open_product("3017620422003".to_string());
+        assert!(viewer.loading == LoadingState::Details);
+
+        handle_answer(&mut viewer);
+        assert!(viewer.loading == LoadingState::None);
+        assert_eq!(viewer.selected_product.as_ref().and_then(|product| product.product_name.as_deref()), Some("Nutella"));
+        assert_eq!(viewer.history.front().and_then(|product| product.code.as_deref()), Some("3017620422003"));
+    }
+
+    #[test]
+    fn cached_details_are_not_shown_for_another_flavor() {
+        let mut viewer = mock_viewer(MockClient {
+            search: Err(AppError::Http(500)),
+            product: Ok(r#"{"code": "3017620422003", "product_name": "Nutella"}"#),
+        });
+        viewer.open_product("3017620422003".to_string());
+        handle_answer(&mut viewer);
+        viewer.open_product("3017620422003".to_string());
+        assert!(viewer.loading == LoadingState::None);
+
+        viewer.flavor = Flavor::Beauty;
+        viewer.update_base_url();
+        viewer.open_product("3017620422003".to_string());
+        assert!(viewer.loading == LoadingState::Details);
+    }
+
+    #[test]
+    fn missing_products_are_reported() {
+        let mut viewer = mock_viewer(MockClient {
+            search: Err(AppError::Http(500)),
+            product: Err(AppError::NotFound),
+        });
+        viewer.open_product("000".to_string());
+
+        handle_answer(&mut viewer);
+        assert!(viewer.loading == LoadingState::None);
+        assert!(viewer.selected_product.is_none());
+        assert_eq!(viewer.error_message, Some(AppError::NotFound));
+    }
+
+    #[test]
+    fn favorites_that_no_longer_resolve_are_kept() {
+        let mut viewer = mock_viewer(MockClient {
+            search: Err(AppError::Http(500)),
+            product: Err(AppError::NotFound),
+        });
+        viewer.favorites.push(Favorite {
+            code: "000".to_string(),
+            product_name: Some("Discontinued".to_string()),
+        });
+        viewer.open_favorites();
+        viewer.open_product("000".to_string());
+
+        handle_answer(&mut viewer);
+        assert_eq!(viewer.error_message, Some(AppError::NotFound));
+        assert_eq!(viewer.favorites.len(), 1);
+        viewer.close_product();
+        assert!(matches!(viewer.view, View::Favorites));
+    }
+
+    #[test]
+    fn typing_searches_once_the_term_settles() {
+        let mut viewer = mock_viewer(MockClient {
+            search: Ok(r#"{"count": 0, "products": []}"#),
+            product: Err(AppError::NotFound),
+        });
+        let ctx = egui::Context::default();
+        for term in ["n", "nu", "nut"] {
+            viewer.search.term = term.to_string();
+            viewer.search_edited = Some(Instant::now());
+            viewer.poll_live_search(&ctx);
+        }
+        assert!(viewer.search.active_query.is_none(), "no request while typing");
+
+        viewer.search_edited = Some(Instant::now() - SEARCH_DEBOUNCE);
+        viewer.poll_live_search(&ctx);
+        assert_eq!(viewer.search.active_query, Some(SearchQuery::Terms("nut".to_string())));
+        assert_eq!(viewer.search.request_id, 1);
+        assert!(viewer.search_history.is_empty(), "only submitted searches are remembered");
+
+        // Editing back to the term shown does not search again
+        viewer.search_edited = Some(Instant::now() - SEARCH_DEBOUNCE);
+        viewer.poll_live_search(&ctx);
+        assert_eq!(viewer.search.request_id, 1);
+    }
+
+    #[test]
+    fn pausing_on_a_barcode_prefix_searches_for_it() {
+        let mut viewer = mock_viewer(MockClient {
+            search: Ok(r#"{"count": 0, "products": []}"#),
+            product: Err(AppError::NotFound),
+        });
+        // A valid EAN-8, on the way to typing 5449000000996
+        viewer.search.term = "54490000".to_string();
+        viewer.search_edited = Some(Instant::now() - SEARCH_DEBOUNCE);
+        viewer.poll_live_search(&egui::Context::default());
+        assert!(matches!(viewer.view, View::SearchResults));
+        assert_eq!(viewer.search.active_query, Some(SearchQuery::Terms("54490000".to_string())));
+
+        viewer.search.term = "5449000000996".to_string();
+        viewer.submit_search();
+        assert!(matches!(viewer.view, View::SearchResults), "keyword searches never open a product");
+        assert_eq!(viewer.search.active_query, Some(SearchQuery::Terms("5449000000996".to_string())));
+    }
+
+    #[test]
+    fn barcode_mode_extracts_pasted_codes() {
+        let mut viewer = mock_viewer(MockClient {
+            search: Err(AppError::Http(500)),
+            product: Err(AppError::NotFound),
+        });
+        viewer.search_mode = SearchMode::Barcode;
+        viewer.search.term = "EAN: 3-017620-422003".to_string();
+        assert_eq!(viewer.barcode_input().as_deref(), Some("3017620422003"));
+        viewer.search.term = "https://world.openfoodfacts.org/product/3017620422003/nutella".to_string();
+        assert_eq!(viewer.barcode_input().as_deref(), Some("3017620422003"));
+        viewer.search.term = "123".to_string();
+        assert_eq!(viewer.barcode_input().as_deref(), Some("123"));
+        viewer.search.term = "nutella".to_string();
+        assert_eq!(viewer.barcode_input(), None);
+
+        viewer.search.term = "EAN: 3-017620-422003".to_string();
+        viewer.submit_barcode();
+        assert!(matches!(viewer.view, View::ProductDetails));
+        assert!(matches!(&viewer.last_request, Some(LastRequest::Details(code)) if code == "3017620422003"));
+    }
+
+    #[test]
+    fn split_results_leave_the_details_loading() {
+        let mut viewer = mock_viewer(MockClient {
+            search: Err(AppError::Http(500)),
+            product: Err(AppError::NotFound),
+        });
+        viewer.open_product("3017620422003".to_string());
+        viewer.split.begin(SearchQuery::Terms("skyr".to_string()), 1);
+        let response = serde_json::from_str(r#"{"count": 0, "products": []}"#).unwrap();
+        viewer.handle_message(Message::SearchResults(Pane::Split, viewer.split.request_id, response));
+        assert!(!viewer.split.loading);
+        assert!(viewer.loading == LoadingState::Details);
+    }
+
+    #[test]
+    fn watched_allergens_are_flagged() {
+        assert_eq!(allergen_flag("Nutella", &[]), ("Nutella".to_string(), None));
+        let (label, warning) = allergen_flag("Nutella", &["nuts", "milk"]);
+        assert_eq!(label, "⚠ Nutella");
+        assert_eq!(warning.as_deref(), Some("Contains watched allergen: nuts, milk"));
+    }
+
+    #[test]
+    fn search_links_restore_the_sort_and_filters() {
+        let mut viewer = mock_viewer(MockClient {
+            search: Ok(r#"{"count": 0, "products": []}"#),
+            product: Err(AppError::NotFound),
+        });
+        let link = SearchLink::parse("offviewer://search?q=skyr&page=2&sort_by=nutriscore_score&max_sugars=10").unwrap();
+        viewer.open_search_link(link);
+        assert!(viewer.result_sort == ResultSort::Server(api::SortBy::NutriScore));
+        assert_eq!(viewer.search.server_sort, Some(api::SortBy::NutriScore));
+        assert_eq!((viewer.search.page, viewer.search.term.as_str()), (2, "skyr"));
+        let sugars = FilterNutrient::ALL.iter().position(|&nutrient| nutrient == FilterNutrient::Sugars).unwrap();
+        assert_eq!(viewer.range_filters[sugars].max, 10.0);
+        assert_eq!(viewer.range_filter_inputs[sugars], [String::new(), "10".to_string()]);
+    }
+}
@@ -0,0 +1,263 @@
+//! Helpers for nutrition calculations.
+use crate::api::Nutriments;
+use crate::format;
+
+/// Units a serving size can be expressed in for per-serving calculations.
+const SERVING_UNITS: &[&str] = &["mg", "g", "kg", "ml", "cl", "dl", "l", "oz"];
+
+/// Extracts the amount and unit of a freeform serving size such as `"30 g"`,
+/// `"1 tasse (250ml)"` or `"2 biscuits (25g)"`.
+///
+/// A quantity in parentheses is preferred since it is usually the weight or
+/// volume of the household measure before it. Returns `None` when no amount
+/// with a known unit can be found, in which case values should stay per 100g.
+pub fn parse_serving_size(s: &str) -> Option<(f64, String)> {
+    let mut rest = s;
+    while let Some(start) = rest.find('(') {
+        let Some(len) = rest[start..].find(')') else {
+            break;
+        };
+        if let Some(quantity) = parse_quantity(&rest[start + 1..start + len]) {
+            return Some(quantity);
+        }
+        rest = &rest[start + len + 1..];
+    }
+    parse_quantity(s)
+}
+
+/// Finds the first `<number> <unit>` pair with a known unit in `s`.
+fn parse_quantity(s: &str) -> Option<(f64, String)> {
+    let mut chars = s.char_indices().peekable();
+    while let Some((start, c)) = chars.next() {
+        if !c.is_ascii_digit() {
+            continue;
+        }
+        let mut end = start + c.len_utf8();
+        while let Some(&(i, c)) = chars.peek() {
+            if !(c.is_ascii_digit() || c == '.' || c == ',') {
+                break;
+            }
+            end = i + c.len_utf8();
+            chars.next();
+        }
+        let Ok(amount) = s[start..end].replace(',', ".").parse::<f64>() else {
+            continue;
+        };
+        let unit: String = s[end..]
+            .trim_start()
+            .chars()
+            .take_while(|c| c.is_alphabetic())
+            .collect::<String>()
+            .to_lowercase();
+        if SERVING_UNITS.contains(&unit.as_str()) {
+            return Some((amount, unit));
+        }
+    }
+    None
+}
+
+/// Which amount of product the nutrition values are given for.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum NutritionBasis {
+    #[default]
+    Per100g,
+    PerServing,
+    /// Scaled to the amount of product that provides 100 kcal.
+    Per100kcal,
+}
+
+/// One line of the nutrition table.
+pub struct NutrientRow {
+    pub name: &'static str,
+    pub unit: &'static str,
+    pub per_100g: Option<f64>,
+}
+
+impl NutrientRow {
+    /// The amount in a serving of `serving` grams (or millilitres).
+    pub fn per_serving(&self, serving: f64) -> Option<f64> {
+        self.per_100g.map(|value| value * serving / 100.0)
+    }
+
+    /// The amount in 100 kcal of product, given the factor from [`per_100kcal_factor`].
+    pub fn per_100kcal(&self, factor: f64) -> Option<f64> {
+        self.per_100g.map(|value| value * factor)
+    }
+}
+
+/// Factor turning per-100g values into per-100-kcal ones.
+///
+/// Returns `None` when the energy is missing, zero or negative, since the
+/// values cannot be scaled to calories then.
+pub fn per_100kcal_factor(energy_kcal_100g: Option<f64>) -> Option<f64> {
+    energy_kcal_100g
+        .filter(|energy| energy.is_finite() && *energy > 0.0)
+        .map(|energy| 100.0 / energy)
+}
+
+/// Reference intakes of an average adult (8400 kJ / 2000 kcal), by nutrient name.
+///
+/// These are the generic EU reference values, not personal recommendations.
+pub const REFERENCE_INTAKES: &[(&str, f64)] = &[
+    ("Energy", 2000.0),
+    ("Fat", 70.0),
+    ("Saturated fat", 20.0),
+    ("Carbohydrates", 260.0),
+    ("Sugars", 90.0),
+    ("Fiber", 25.0),
+    ("Proteins", 50.0),
+    ("Salt", 6.0),
+];
+
+/// Share of the daily reference intake of `nutrient` that `amount` represents, in percent.
+pub fn percent_daily_value(nutrient: &str, amount: f64) -> Option<f64> {
+    REFERENCE_INTAKES
+        .iter()
+        .find(|(name, _)| *name == nutrient)
+        .map(|(_, reference)| amount / reference * 100.0)
+}
+
+/// The nutrition table lines, in the usual label order.
+pub fn nutrient_rows(nutriments: &Nutriments) -> Vec<NutrientRow> {
+    let row = |name, unit, per_100g| NutrientRow { name, unit, per_100g };
+    vec![
+        row("Energy", "kcal", nutriments.energy_kcal_100g),
+        row("Fat", "g", nutriments.fat_100g),
+        row("Saturated fat", "g", nutriments.saturated_fat_100g),
+        row("Carbohydrates", "g", nutriments.carbohydrates_100g),
+        row("Sugars", "g", nutriments.sugars_100g),
+        row("Fiber", "g", nutriments.fiber_100g),
+        row("Proteins", "g", nutriments.proteins_100g),
+        row("Salt", "g", nutriments.salt_100g),
+    ]
+}
+
+/// The serving size in grams (or millilitres), the unit the per-100g values refer to.
+pub fn serving_base_amount(serving_size: &str) -> Option<f64> {
+    let (amount, unit) = parse_serving_size(serving_size)?;
+    let factor = match unit.as_str() {
+        "mg" => 0.001,
+        "g" | "ml" => 1.0,
+        "cl" => 10.0,
+        "dl" => 100.0,
+        "kg" | "l" => 1000.0,
+        "oz" => 28.35,
+        _ => return None,
+    };
+    Some(amount * factor)
+}
+
+/// The nutrition table as tab-separated values for pasting into a spreadsheet.
+///
+/// A second column is included when `basis` is [`NutritionBasis::PerServing`]
+/// and the serving size is known, or [`NutritionBasis::Per100kcal`] and the
+/// energy is known.
+pub fn nutrition_tsv(rows: &[NutrientRow], serving: Option<f64>, basis: NutritionBasis, locale: &str) -> String {
+    let energy = rows.iter().find(|row| row.name == "Energy").and_then(|row| row.per_100g);
+    let second_column = match basis {
+        NutritionBasis::Per100g => None,
+        NutritionBasis::PerServing => serving.map(|serving| ("Per serving", serving / 100.0)),
+        NutritionBasis::Per100kcal => per_100kcal_factor(energy).map(|factor| ("Per 100 kcal", factor)),
+    };
+    let value = |value: Option<f64>| value.map_or(String::new(), |value| format::fmt_number(value, 1, locale));
+    let mut tsv = String::from("Nutrient\tPer 100g");
+    if let Some((title, _)) = second_column {
+        tsv.push_str(&format!("\t{}", title));
+    }
+    for row in rows {
+        tsv.push_str(&format!("\n{} ({})\t{}", row.name, row.unit, value(row.per_100g)));
+        if let Some((_, factor)) = second_column {
+            tsv.push_str(&format!("\t{}", value(row.per_100g.map(|value| value * factor))));
+        }
+    }
+    tsv
+}
+
+/// A nutriment search results can be filtered by.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum FilterNutrient {
+    Energy,
+    Sugars,
+    Salt,
+}
+
+impl FilterNutrient {
+    pub const ALL: [FilterNutrient; 3] = [FilterNutrient::Energy, FilterNutrient::Sugars, FilterNutrient::Salt];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            FilterNutrient::Energy => "Energy",
+            FilterNutrient::Sugars => "Sugars",
+            FilterNutrient::Salt => "Salt",
+        }
+    }
+
+    /// Name of the nutrient in links, e.g. `"sugars"`.
+    pub fn key(self) -> &'static str {
+        match self {
+            FilterNutrient::Energy => "energy",
+            FilterNutrient::Sugars => "sugars",
+            FilterNutrient::Salt => "salt",
+        }
+    }
+
+    pub fn unit(self) -> &'static str {
+        match self {
+            FilterNutrient::Energy => "kcal",
+            FilterNutrient::Sugars | FilterNutrient::Salt => "g",
+        }
+    }
+
+    /// Upper bound of the filter range, beyond any real product per 100g.
+    pub fn max_value(self) -> f64 {
+        match self {
+            FilterNutrient::Energy => 900.0,
+            FilterNutrient::Sugars => 100.0,
+            FilterNutrient::Salt => 10.0,
+        }
+    }
+
+    /// The amount of this nutriment in 100g of product.
+    pub fn value(self, nutriments: &Nutriments) -> Option<f64> {
+        match self {
+            FilterNutrient::Energy => nutriments.energy_kcal_100g,
+            FilterNutrient::Sugars => nutriments.sugars_100g,
+            FilterNutrient::Salt => nutriments.salt_100g,
+        }
+    }
+}
+
+/// Keeps the products whose amount of `nutrient` per 100g lies in `min..=max`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct RangeFilter {
+    pub nutrient: FilterNutrient,
+    pub min: f64,
+    pub max: f64,
+}
+
+impl RangeFilter {
+    /// A filter over the whole range, which lets every product through.
+    pub fn new(nutrient: FilterNutrient) -> Self {
+        Self {
+            nutrient,
+            min: 0.0,
+            max: nutrient.max_value(),
+        }
+    }
+
+    /// Whether the range has been narrowed, so that the filter excludes something.
+    pub fn is_active(&self) -> bool {
+        self.min > 0.0 || self.max < self.nutrient.max_value()
+    }
+
+    /// Whether `nutriments` pass the filter; unknown amounts pass only with `include_unknown`.
+    pub fn matches(&self, nutriments: &Nutriments, include_unknown: bool) -> bool {
+        if !self.is_active() {
+            return true;
+        }
+        match self.nutrient.value(nutriments) {
+            Some(value) => (self.min..=self.max).contains(&value),
+            None => include_unknown,
+        }
+    }
+}
@@ -0,0 +1,630 @@
+use httpmock::prelude::*;
+use test_egui::api::{download_image, edit_product_url, fetch_product, lookup_product, search_products, with_retry, ApiConfig, AppError, Credentials, Flavor, RequestHooks, SearchQuery, SortBy};
+
+fn config_for(server: &MockServer) -> ApiConfig {
+    ApiConfig {
+        base_url: server.base_url(),
+        ..ApiConfig::default()
+    }
+}
+
+#[test]
+fn search_returns_parsed_products() {
+    let server = MockServer::start();
+    let mock = server.mock(|when, then| {
+        when.method(GET).path("/cgi/search.pl").query_param("search_terms", "milk");
+        then.status(200).body(
+            r#"{"count": 2, "products": [
+                {"code": "111", "product_name": "Whole milk"},
+                {"code": "222"}
+            ]}"#,
+        );
+    });
+
+    let products = search_products(
+        &config_for(&server),
+        &SearchQuery::Terms("milk".to_string()),
+        1,
+        None,
+        &RequestHooks::default(),
+    )
+    .unwrap()
+    .products;
+
+    mock.assert();
+    assert_eq!(products.len(), 2);
+    assert_eq!(products[0].code.as_deref(), Some("111"));
+    assert_eq!(products[0].product_name.as_deref(), Some("Whole milk"));
+    assert_eq!(products[1].product_name, None);
+}
+
+#[test]
+fn search_requests_the_given_page() {
+    let server = MockServer::start();
+    let mock = server.mock(|when, then| {
+        when.method(GET).path("/cgi/search.pl").query_param("page", "3");
+        then.status(200).body(r#"{"count": 45, "page": "3", "page_size": 20, "products": []}"#);
+    });
+
+    let response = search_products(&config_for(&server), &SearchQuery::Terms("milk".to_string()), 3, None, &RequestHooks::default()).unwrap();
+
+    mock.assert();
+    assert_eq!(response.page, Some(3));
+    assert_eq!(response.page_count(), 3);
+}
+
+#[test]
+fn page_count_falls_back_to_the_reported_one() {
+    let response: test_egui::api::SearchResponse = serde_json::from_str(r#"{"page": 1, "page_count": "7", "products": []}"#).unwrap();
+
+    assert_eq!(response.reported_page_count, Some(7));
+    assert_eq!(response.page_count(), 7);
+}
+
+#[test]
+fn category_searches_sort_by_popularity() {
+    let server = MockServer::start();
+    let mock = server.mock(|when, then| {
+        when.method(GET)
+            .path("/cgi/search.pl")
+            .query_param("tag_0", "en:snacks")
+            .query_param("sort_by", "unique_scans_n");
+        then.status(200).body(r#"{"count": 0, "products": []}"#);
+    });
+
+    search_products(&config_for(&server), &SearchQuery::Category("en:snacks".to_string()), 1, None, &RequestHooks::default()).unwrap();
+
+    mock.assert();
+}
+
+#[test]
+fn credentials_are_sent_only_where_wanted() {
+    let credentials = Credentials {
+        username: "off".to_string(),
+        password: "off".to_string(),
+    };
+    let mut config = ApiConfig {
+        credentials: Some(credentials.clone()),
+        ..ApiConfig::default()
+    };
+    assert!(!config.is_staging());
+    assert_eq!(config.credentials_for("https://world.openfoodfacts.org/api/v0/product/1.json"), None);
+
+    config.base_url = Flavor::Food.staging_url();
+    assert!(config.is_staging());
+    assert_eq!(config.credentials_for("https://world.openfoodfacts.net/api/v0/product/1.json"), Some(&credentials));
+    assert_eq!(config.credentials_for("https://images.openfoodfacts.net/images/1.jpg"), None);
+    assert_eq!(config.credentials_for("https://world.openfoodfacts.net.example.com/"), None);
+}
+
+#[test]
+fn requests_carry_basic_auth_when_forced() {
+    let server = MockServer::start();
+    let mock = server.mock(|when, then| {
+        when.method(GET)
+            .path("/api/v0/product/123.json")
+            .header("authorization", "Basic b2ZmOm9mZg==");
+        then.status(200).body(r#"{"status": 1, "product": {"code": "123"}}"#);
+    });
+    let config = ApiConfig {
+        credentials: Some(Credentials {
+            username: "off".to_string(),
+            password: "off".to_string(),
+        }),
+        always_authenticate: true,
+        ..config_for(&server)
+    };
+
+    fetch_product(&config, "123", &RequestHooks::default()).unwrap();
+
+    mock.assert();
+}
+
+#[test]
+fn finished_requests_are_recorded() {
+    use std::sync::{Arc, Mutex};
+
+    let server = MockServer::start();
+    server.mock(|when, then| {
+        when.method(GET).path("/api/v0/product/123.json");
+        then.status(200).body(r#"{"status": 1, "product": {"code": "123"}}"#);
+    });
+    server.mock(|when, then| {
+        when.method(GET).path("/api/v0/product/404.json");
+        then.status(404);
+    });
+    let records = Arc::new(Mutex::new(Vec::new()));
+    let hooks = RequestHooks {
+        on_request: Some(Arc::new({
+            let records = records.clone();
+            move |record| records.lock().unwrap().push(record)
+        })),
+        ..RequestHooks::default()
+    };
+
+    fetch_product(&config_for(&server), "123", &hooks).unwrap();
+    assert!(fetch_product(&config_for(&server), "404", &hooks).is_err());
+
+    let records = records.lock().unwrap();
+    assert_eq!(records.len(), 2);
+    assert!(records[0].url.ends_with("/api/v0/product/123.json?lc=en"));
+    assert_eq!((records[0].status, records[0].bytes), (Some(200), Some(41)));
+    assert_eq!((records[1].status, records[1].bytes), (Some(404), None));
+}
+
+#[test]
+fn search_terms_are_percent_encoded() {
+    use std::sync::{Arc, Mutex};
+
+    let server = MockServer::start();
+    let mock = server.mock(|when, then| {
+        when.method(GET)
+            .path("/cgi/search.pl")
+            .query_param("search_terms", "sel & poivre #1")
+            .query_param("json", "1");
+        then.status(200).body(r#"{"count": 1, "products": [{"code": "111", "product_name": "Sel et poivre"}]}"#);
+    });
+    let urls = Arc::new(Mutex::new(Vec::new()));
+    let hooks = RequestHooks {
+        on_request: Some(Arc::new({
+            let urls = urls.clone();
+            move |record| urls.lock().unwrap().push(record.url)
+        })),
+        ..RequestHooks::default()
+    };
+
+    let response = search_products(&config_for(&server), &SearchQuery::Terms("sel & poivre #1".to_string()), 1, None, &hooks).unwrap();
+
+    mock.assert();
+    assert_eq!(response.products[0].product_name.as_deref(), Some("Sel et poivre"));
+    assert!(urls.lock().unwrap()[0].contains("?search_terms=sel%20%26%20poivre%20%231&search_simple=1"));
+}
+
+#[test]
+fn edit_links_percent_encode_the_code() {
+    let config = ApiConfig::default();
+    assert_eq!(
+        edit_product_url(&config, "123&type=delete"),
+        format!("{}/cgi/product.pl?type=edit&code=123%26type%3Ddelete", config.base_url)
+    );
+}
+
+#[test]
+fn searches_pass_the_server_sort() {
+    let server = MockServer::start();
+    let mock = server.mock(|when, then| {
+        when.method(GET)
+            .path("/cgi/search.pl")
+            .query_param("search_terms", "milk")
+            .query_param("sort_by", "nutriscore_score");
+        then.status(200).body(r#"{"count": 0, "products": []}"#);
+    });
+
+    search_products(&config_for(&server), &SearchQuery::Terms("milk".to_string()), 1, Some(SortBy::NutriScore), &RequestHooks::default()).unwrap();
+
+    mock.assert();
+}
+
+#[test]
+fn fetch_returns_parsed_product() {
+    let server = MockServer::start();
+    server.mock(|when, then| {
+        when.method(GET).path("/api/v0/product/3017620422003.json");
+        then.status(200).body(
+            r#"{"status": 1, "product": {
+                "code": "3017620422003",
+                "product_name": "Nutella",
+                "nutrition_grades": "e",
+                "nutriments": {"sugars_100g": 56.3}
+            }}"#,
+        );
+    });
+
+    let product = fetch_product(&config_for(&server), "3017620422003", &RequestHooks::default()).unwrap();
+
+    assert_eq!(product.code, "3017620422003");
+    assert_eq!(product.product_name.as_deref(), Some("Nutella"));
+    assert_eq!(product.nutriscore(), Some('e'));
+    assert_eq!(product.nutriments.sugars_100g, Some(56.3));
+}
+
+#[test]
+fn fetch_maps_status_zero_to_not_found() {
+    let server = MockServer::start();
+    server.mock(|when, then| {
+        when.method(GET).path("/api/v0/product/000.json");
+        then.status(200).body(r#"{"status": 0, "status_verbose": "product not found", "code": "000"}"#);
+    });
+
+    let result = fetch_product(&config_for(&server), "000", &RequestHooks::default());
+
+    assert_eq!(result.err(), Some(AppError::NotFound));
+}
+
+#[test]
+fn lookup_retries_the_zero_padded_code() {
+    let server = MockServer::start();
+    let missing = server.mock(|when, then| {
+        when.method(GET).path("/api/v0/product/36000291452.json");
+        then.status(200).body(r#"{"status": 0}"#);
+    });
+    server.mock(|when, then| {
+        when.method(GET).path("/api/v0/product/036000291452.json");
+        then.status(200).body(r#"{"status": 0}"#);
+    });
+    server.mock(|when, then| {
+        when.method(GET).path("/api/v0/product/0036000291452.json");
+        then.status(200).body(r#"{"status": 1, "product": {"code": "0036000291452", "product_name": "Tissues"}}"#);
+    });
+
+    let product = lookup_product(&config_for(&server), "36000291452", &RequestHooks::default()).unwrap();
+
+    missing.assert();
+    assert_eq!(product.code, "0036000291452");
+}
+
+#[test]
+fn lookup_reports_not_found_after_every_variant() {
+    let server = MockServer::start();
+    let mock = server.mock(|when, then| {
+        when.method(GET);
+        then.status(200).body(r#"{"status": 0}"#);
+    });
+
+    let result = lookup_product(&config_for(&server), "036000291452", &RequestHooks::default());
+
+    mock.assert_hits(3);
+    assert_eq!(result.err(), Some(AppError::NotFound));
+}
+
+#[test]
+fn image_downloads_report_their_progress() {
+    let server = MockServer::start();
+    let image = vec![7u8; 100_000];
+    server.mock(|when, then| {
+        when.method(GET).path("/images/front.jpg");
+        then.status(200).body(image.clone());
+    });
+
+    let mut progress = Vec::new();
+    let bytes = download_image(
+        &config_for(&server),
+        &server.url("/images/front.jpg"),
+        &RequestHooks::default(),
+        |fraction| progress.push(fraction),
+    )
+    .unwrap();
+
+    assert_eq!(bytes, image);
+    assert!(progress.len() > 1);
+    assert!(progress.windows(2).all(|pair| pair[0] <= pair[1]));
+    assert_eq!(progress.last(), Some(&Some(1.0)));
+}
+
+#[test]
+fn server_errors_map_to_http_error() {
+    let server = MockServer::start();
+    server.mock(|when, then| {
+        when.method(GET).path("/cgi/search.pl");
+        then.status(500).body("Internal Server Error");
+    });
+
+    let result = search_products(
+        &config_for(&server),
+        &SearchQuery::Terms("milk".to_string()),
+        1,
+        None,
+        &RequestHooks::default(),
+    );
+
+    assert_eq!(result.err(), Some(AppError::Http(500)));
+}
+
+#[test]
+fn malformed_json_maps_to_parse_error() {
+    let server = MockServer::start();
+    server.mock(|when, then| {
+        when.method(GET).path("/api/v0/product/123.json");
+        then.status(200).body(r#"{"status": 1, "product": {"#);
+    });
+
+    let result = fetch_product(&config_for(&server), "123", &RequestHooks::default());
+
+    assert!(matches!(result, Err(AppError::Parse(_))), "unexpected result: {:?}", result.err());
+}
+
+#[test]
+fn empty_body_maps_to_network_error() {
+    let server = MockServer::start();
+    server.mock(|when, then| {
+        when.method(GET).path("/cgi/search.pl");
+        then.status(200).body(" \n");
+    });
+
+    let result = search_products(&config_for(&server), &SearchQuery::Terms("nutella".to_string()), 1, None, &RequestHooks::default());
+
+    assert_eq!(result.err(), Some(AppError::Network("Empty response from server".to_string())));
+}
+
+#[test]
+fn slow_searches_time_out() {
+    use std::time::Duration;
+
+    let server = MockServer::start();
+    server.mock(|when, then| {
+        when.method(GET).path("/cgi/search.pl");
+        then.status(200).delay(Duration::from_millis(500)).body(r#"{"count": 0, "products": []}"#);
+    });
+    let config = ApiConfig {
+        timeout: Duration::from_millis(50),
+        retry_attempts: 1,
+        ..config_for(&server)
+    };
+
+    let result = search_products(&config, &SearchQuery::Terms("milk".to_string()), 1, None, &RequestHooks::default());
+
+    assert_eq!(result.err(), Some(AppError::Timeout));
+}
+
+#[test]
+fn app_errors_are_std_errors() {
+    let error: Box<dyn std::error::Error> = Box::new(AppError::Http(503));
+
+    assert_eq!(error.to_string(), "Server returned HTTP 503");
+}
+
+#[test]
+fn reqwest_timeouts_map_to_timeout() {
+    use std::time::Duration;
+
+    let server = MockServer::start();
+    server.mock(|when, then| {
+        when.method(GET).path("/slow");
+        then.status(200).delay(Duration::from_millis(500)).body("{}");
+    });
+    let client = reqwest::blocking::Client::builder().timeout(Duration::from_millis(50)).build().unwrap();
+
+    let error = client.get(server.url("/slow")).send().unwrap_err();
+
+    assert_eq!(AppError::from(error), AppError::Timeout);
+}
+
+#[test]
+fn decode_errors_map_to_parse_error() {
+    let server = MockServer::start();
+    server.mock(|when, then| {
+        when.method(GET).path("/html");
+        then.status(200).body("<html></html>");
+    });
+
+    let error = reqwest::blocking::get(server.url("/html")).unwrap().json::<serde_json::Value>().unwrap_err();
+    assert!(matches!(AppError::from(error), AppError::Parse(_)));
+
+    let error = serde_json::from_str::<serde_json::Value>("{").unwrap_err();
+    assert!(matches!(AppError::from(error), AppError::Parse(_)));
+}
+
+#[test]
+fn watched_allergens_match_tags_by_name() {
+    let tags = vec!["en:milk".to_string(), "en:nuts".to_string()];
+    let watchlist = vec!["Nuts".to_string(), "gluten".to_string()];
+
+    assert_eq!(test_egui::api::watched_allergens(&tags, &watchlist), vec!["Nuts"]);
+}
+
+#[test]
+fn concurrent_requests_are_limited() {
+    use std::time::{Duration, Instant};
+    use test_egui::api::MAX_CONCURRENT_REQUESTS;
+
+    let server = MockServer::start();
+    server.mock(|when, then| {
+        when.method(GET).path("/api/v0/product/123.json");
+        then.status(200)
+            .delay(Duration::from_millis(300))
+            .body(r#"{"status": 1, "product": {"code": "123"}}"#);
+    });
+    let config = config_for(&server);
+
+    let started = Instant::now();
+    let workers: Vec<_> = (0..MAX_CONCURRENT_REQUESTS * 2)
+        .map(|_| {
+            let config = config.clone();
+            std::thread::spawn(move || fetch_product(&config, "123", &RequestHooks::default()))
+        })
+        .collect();
+    for worker in workers {
+        assert!(worker.join().unwrap().is_ok());
+    }
+
+    // Twice as many requests as permits need at least two rounds
+    assert!(started.elapsed() >= Duration::from_millis(600), "took {:?}", started.elapsed());
+}
+
+#[test]
+fn rate_limited_requests_free_their_permit_while_waiting() {
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::sync::{mpsc, Arc};
+    use std::time::{Duration, Instant};
+    use test_egui::api::MAX_CONCURRENT_REQUESTS;
+
+    let server = MockServer::start();
+    server.mock(|when, then| {
+        when.method(GET).path("/api/v0/product/429.json");
+        then.status(429).header("Retry-After", "10");
+    });
+    server.mock(|when, then| {
+        when.method(GET).path("/api/v0/product/123.json");
+        then.status(200).body(r#"{"status": 1, "product": {"code": "123"}}"#);
+    });
+    let config = config_for(&server);
+    let cancel = Arc::new(AtomicBool::new(false));
+    let (waiting, waits) = mpsc::channel();
+    let workers: Vec<_> = (0..MAX_CONCURRENT_REQUESTS)
+        .map(|_| {
+            let config = config.clone();
+            let hooks = RequestHooks {
+                cancel: cancel.clone(),
+                on_rate_limit: Some(Arc::new({
+                    let waiting = waiting.clone();
+                    move |_| {
+                        let _ = waiting.send(());
+                    }
+                })),
+                ..RequestHooks::default()
+            };
+            std::thread::spawn(move || fetch_product(&config, "429", &hooks))
+        })
+        .collect();
+    for _ in 0..MAX_CONCURRENT_REQUESTS {
+        waits.recv_timeout(Duration::from_secs(5)).unwrap();
+    }
+
+    // Every other request is waiting out its Retry-After, without holding a permit
+    let started = Instant::now();
+    assert!(fetch_product(&config, "123", &RequestHooks::default()).is_ok());
+    assert!(started.elapsed() < Duration::from_secs(5), "took {:?}", started.elapsed());
+
+    cancel.store(true, Ordering::Relaxed);
+    for worker in workers {
+        assert_eq!(worker.join().unwrap().err(), Some(AppError::Cancelled));
+    }
+}
+
+/// Hooks recording the waits they are asked for instead of sleeping.
+fn recording_waits() -> (RequestHooks, std::sync::Arc<std::sync::Mutex<Vec<std::time::Duration>>>) {
+    use std::sync::{Arc, Mutex};
+
+    let waits = Arc::new(Mutex::new(Vec::new()));
+    let hooks = RequestHooks {
+        wait: Some(Arc::new({
+            let waits = waits.clone();
+            move |duration| waits.lock().unwrap().push(duration)
+        })),
+        ..RequestHooks::default()
+    };
+    (hooks, waits)
+}
+
+#[test]
+fn rate_limited_requests_are_retried_after_the_delay() {
+    use std::sync::{mpsc, Arc, Mutex};
+    use std::time::Duration;
+
+    let server = MockServer::start();
+    let mut limited = server.mock(|when, then| {
+        when.method(GET).path("/api/v0/product/123.json");
+        then.status(429).header("Retry-After", "7");
+    });
+    // The wait blocks until the server has been told to answer the retry
+    let (waiting, waits) = mpsc::channel();
+    let (resume, resumed) = mpsc::channel::<()>();
+    let resumed = Mutex::new(resumed);
+    let hooks = RequestHooks {
+        wait: Some(Arc::new(move |duration| {
+            waiting.send(duration).unwrap();
+            resumed.lock().unwrap().recv().unwrap();
+        })),
+        ..RequestHooks::default()
+    };
+    let config = config_for(&server);
+    let worker = std::thread::spawn(move || fetch_product(&config, "123", &hooks));
+
+    assert_eq!(waits.recv_timeout(Duration::from_secs(5)), Ok(Duration::from_secs(7)));
+    limited.assert_hits(1);
+    limited.delete();
+    let answered = server.mock(|when, then| {
+        when.method(GET).path("/api/v0/product/123.json");
+        then.status(200).body(r#"{"status": 1, "product": {"code": "123"}}"#);
+    });
+    resume.send(()).unwrap();
+
+    assert_eq!(worker.join().unwrap().map(|product| product.code).ok().as_deref(), Some("123"));
+    answered.assert_hits(1);
+}
+
+#[test]
+fn requests_still_rate_limited_fail_after_one_retry() {
+    use std::time::Duration;
+
+    let server = MockServer::start();
+    let limited = server.mock(|when, then| {
+        when.method(GET).path("/api/v0/product/123.json");
+        then.status(429).header("Retry-After", "2");
+    });
+    let (hooks, waits) = recording_waits();
+
+    let result = fetch_product(&config_for(&server), "123", &hooks);
+
+    assert_eq!(result.err(), Some(AppError::Network("Rate limited by the server".to_string())));
+    limited.assert_hits(2);
+    assert_eq!(waits.lock().unwrap().as_slice(), [Duration::from_secs(2)]);
+}
+
+#[test]
+fn retry_after_is_capped() {
+    use std::time::Duration;
+
+    let server = MockServer::start();
+    server.mock(|when, then| {
+        when.method(GET).path("/api/v0/product/123.json");
+        then.status(429).header("Retry-After", "3600");
+    });
+    let (hooks, waits) = recording_waits();
+
+    let _ = fetch_product(&config_for(&server), "123", &hooks);
+
+    assert_eq!(waits.lock().unwrap().as_slice(), [Duration::from_secs(30)]);
+}
+
+#[test]
+fn flaky_requests_eventually_succeed() {
+    let calls = std::cell::Cell::new(0);
+    let result = with_retry(3, &RequestHooks::default(), || {
+        calls.set(calls.get() + 1);
+        if calls.get() < 3 {
+            Err(AppError::Network("connection reset".to_string()))
+        } else {
+            Ok("body")
+        }
+    });
+    assert_eq!(result, Ok("body"));
+    assert_eq!(calls.get(), 3);
+
+    calls.set(0);
+    let result: Result<(), AppError> = with_retry(2, &RequestHooks::default(), || {
+        calls.set(calls.get() + 1);
+        Err(AppError::Timeout)
+    });
+    assert_eq!(result, Err(AppError::Timeout));
+    assert_eq!(calls.get(), 2);
+}
+
+#[test]
+fn cancelling_stops_the_backoff() {
+    use std::sync::atomic::AtomicBool;
+    use std::sync::Arc;
+
+    let hooks = RequestHooks {
+        cancel: Arc::new(AtomicBool::new(true)),
+        ..RequestHooks::default()
+    };
+    let calls = std::cell::Cell::new(0);
+    let result: Result<(), AppError> = with_retry(3, &hooks, || {
+        calls.set(calls.get() + 1);
+        Err(AppError::Timeout)
+    });
+    assert_eq!(result, Err(AppError::Cancelled));
+    assert_eq!(calls.get(), 1);
+}
+
+#[test]
+fn not_found_is_never_retried() {
+    let calls = std::cell::Cell::new(0);
+    let result: Result<(), AppError> = with_retry(5, &RequestHooks::default(), || {
+        calls.set(calls.get() + 1);
+        Err(AppError::NotFound)
+    });
+    assert_eq!(result, Err(AppError::NotFound));
+    assert_eq!(calls.get(), 1);
+}
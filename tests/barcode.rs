@@ -0,0 +1,50 @@
+use test_egui::barcode::{code_variants, extract_code, has_valid_check_digit};
+
+#[test]
+fn pads_upc_a_codes_missing_their_leading_zero() {
+    assert_eq!(code_variants("36000291452"), vec!["36000291452", "036000291452", "0036000291452"]);
+}
+
+#[test]
+fn tries_upc_a_codes_as_ean13() {
+    assert_eq!(code_variants("036000291452"), vec!["036000291452", "36000291452", "0036000291452"]);
+}
+
+#[test]
+fn tries_ean13_codes_without_their_leading_zero() {
+    assert_eq!(code_variants("0036000291452"), vec!["0036000291452", "036000291452"]);
+    assert_eq!(code_variants("3017620422003"), vec!["3017620422003"]);
+}
+
+#[test]
+fn leaves_non_numeric_codes_alone() {
+    assert_eq!(code_variants(" abc-123 "), vec!["abc-123"]);
+    assert_eq!(code_variants(""), vec![""]);
+}
+
+#[test]
+fn validates_check_digits() {
+    assert!(has_valid_check_digit("3017620422003"));
+    assert!(has_valid_check_digit("036000291452"));
+    assert!(has_valid_check_digit("96385074"));
+    assert!(!has_valid_check_digit("3017620422004"));
+    assert!(!has_valid_check_digit("30176a0422003"));
+}
+
+#[test]
+fn extracts_codes_from_pasted_text() {
+    assert_eq!(extract_code("EAN: 3-017620-422003").as_deref(), Some("3017620422003"));
+    assert_eq!(
+        extract_code("https://world.openfoodfacts.org/product/3017620422003/nutella-ferrero").as_deref(),
+        Some("3017620422003")
+    );
+    assert_eq!(extract_code("2 x UPC 036000 291452 @ 1.99").as_deref(), Some("036000291452"));
+    assert_eq!(extract_code("receipt 12345678"), None);
+    assert_eq!(extract_code("no code here"), None);
+}
+
+#[test]
+fn long_runs_of_digit_groups_are_scanned() {
+    let text = format!("{}3017620422003", "1 ".repeat(1_500));
+    assert_eq!(extract_code(&text).as_deref(), Some("3017620422003"));
+}
@@ -0,0 +1,59 @@
+use std::time::Duration;
+use test_egui::api::ProductDetails;
+use test_egui::cache::{DetailsCache, MAX_ENTRIES};
+
+const TTL: Duration = Duration::from_secs(3600);
+const SOURCE: &str = "https://world.openfoodfacts.org en";
+
+fn product(code: &str) -> ProductDetails {
+    serde_json::from_str(&format!(r#"{{"code": "{}", "product_name": "Product {}"}}"#, code, code)).unwrap()
+}
+
+#[test]
+fn expired_entries_are_ignored() {
+    let mut cache = DetailsCache::default();
+    cache.insert(SOURCE, product("123"), 1_000);
+
+    assert!(cache.get(SOURCE, "123", TTL, 1_000 + 3_600).is_some());
+    assert!(cache.get(SOURCE, "123", TTL, 1_000 + 3_601).is_none());
+    assert!(cache.get(SOURCE, "456", TTL, 1_000).is_none());
+}
+
+#[test]
+fn oldest_entries_are_evicted_beyond_the_cap() {
+    let mut cache = DetailsCache::default();
+    for i in 0..MAX_ENTRIES as u64 + 5 {
+        cache.insert(SOURCE, product(&i.to_string()), 1_000 + i);
+    }
+
+    assert_eq!(cache.len(), MAX_ENTRIES);
+    assert!(cache.get(SOURCE, "4", TTL, 2_000).is_none());
+    assert!(cache.get(SOURCE, "5", TTL, 2_000).is_some());
+}
+
+#[test]
+fn survives_a_save_and_load_round_trip() {
+    let path = std::env::temp_dir().join(format!("test_egui_cache_{}.json", std::process::id()));
+    let mut cache = DetailsCache::default();
+    cache.insert(SOURCE, product("fresh"), 10_000);
+    cache.insert(SOURCE, product("stale"), 1_000);
+
+    cache.save(&path, TTL, 10_000).unwrap();
+    let loaded = DetailsCache::load(&path);
+    std::fs::remove_file(&path).unwrap();
+
+    assert_eq!(loaded.len(), 1);
+    let fresh = loaded.get(SOURCE, "fresh", TTL, 10_000).unwrap();
+    assert_eq!(fresh.product_name.as_deref(), Some("Product fresh"));
+}
+
+#[test]
+fn missing_or_corrupt_files_load_empty() {
+    let path = std::env::temp_dir().join(format!("test_egui_corrupt_{}.json", std::process::id()));
+    assert!(DetailsCache::load(&path).is_empty());
+
+    std::fs::write(&path, "{ not json").unwrap();
+    let loaded = DetailsCache::load(&path);
+    std::fs::remove_file(&path).unwrap();
+    assert!(loaded.is_empty());
+}
@@ -0,0 +1,31 @@
+use test_egui::api::Product;
+use test_egui::export::{results_csv, results_json};
+
+fn products() -> Vec<Product> {
+    serde_json::from_str(
+        r#"[
+            {"code": "111", "product_name": "Milk, \"whole\"", "allergens_tags": ["en:milk"], "nutriments": {"energy-kcal_100g": 64, "salt_100g": 0.1}},
+            {"code": "222"}
+        ]"#,
+    )
+    .unwrap()
+}
+
+#[test]
+fn csv_quotes_fields_and_leaves_unknown_values_empty() {
+    assert_eq!(
+        results_csv(&products()),
+        "code,product_name,energy_kcal_100g,sugars_100g,salt_100g,allergens\n\
+         111,\"Milk, \"\"whole\"\"\",64,,0.1,en:milk\n\
+         222,,,,,\n"
+    );
+}
+
+#[test]
+fn json_round_trips() {
+    let parsed: Vec<Product> = serde_json::from_str(&results_json(&products())).unwrap();
+
+    assert_eq!(parsed.len(), 2);
+    assert_eq!(parsed[0].product_name.as_deref(), Some("Milk, \"whole\""));
+    assert_eq!(parsed[0].nutriments.energy_kcal_100g, Some(64.0));
+}
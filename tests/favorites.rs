@@ -0,0 +1,30 @@
+use test_egui::api::AppError;
+use test_egui::favorites::{export, import, merge, Favorite};
+
+fn favorite(code: &str, name: &str) -> Favorite {
+    Favorite {
+        code: code.to_string(),
+        product_name: Some(name.to_string()),
+    }
+}
+
+#[test]
+fn export_round_trips() {
+    let favorites = vec![favorite("111", "Milk"), favorite("222", "Bread")];
+    assert_eq!(import(&export(&favorites)).unwrap(), favorites);
+}
+
+#[test]
+fn merge_skips_known_codes() {
+    let mut favorites = vec![favorite("111", "Milk")];
+    let added = merge(&mut favorites, vec![favorite("111", "Milk (renamed)"), favorite("222", "Bread")]);
+    assert_eq!(added, 1);
+    assert_eq!(favorites, vec![favorite("111", "Milk"), favorite("222", "Bread")]);
+}
+
+#[test]
+fn rejects_malformed_files() {
+    assert!(matches!(import("[1, 2, 3]"), Err(AppError::Parse(_))));
+    assert!(matches!(import(r#"{"favorites": [{"product_name": "No code"}]}"#), Err(AppError::Parse(_))));
+    assert!(matches!(import(r#"{"favorites": [{"code": " "}]}"#), Err(AppError::Parse(_))));
+}
@@ -0,0 +1,63 @@
+use test_egui::format::{fmt_bytes, fmt_date, fmt_grams, fmt_number, language_code, parse_locale_f64, single_line};
+
+#[test]
+fn formats_numbers_with_locale_separator() {
+    assert_eq!(fmt_grams(12.46, "en"), "12.5 g");
+    assert_eq!(fmt_grams(12.46, "fr"), "12,5 g");
+    assert_eq!(fmt_number(3.0, 2, "de"), "3,00");
+}
+
+#[test]
+fn formats_dates_in_locale_style() {
+    // 2024-03-05 12:00:00 UTC
+    let timestamp = 1_709_640_000;
+    assert_eq!(fmt_date(timestamp, "en"), "2024-03-05");
+    assert_eq!(fmt_date(timestamp, "fr"), "05/03/2024");
+    assert_eq!(fmt_date(timestamp, "de"), "05.03.2024");
+}
+
+#[test]
+fn sizes_use_binary_units() {
+    assert_eq!(fmt_bytes(512), "512 B");
+    assert_eq!(fmt_bytes(14_540), "14.2 KiB");
+    assert_eq!(fmt_bytes(3 * 1024 * 1024), "3.0 MiB");
+}
+
+#[test]
+fn names_with_newlines_fit_on_one_line() {
+    assert_eq!(single_line("Pâte à tartiner\n  aux noisettes\r\n", 80), "Pâte à tartiner aux noisettes");
+}
+
+#[test]
+fn overly_long_names_are_truncated() {
+    let name = "Chocolate ".repeat(25);
+    assert!(name.chars().count() > 200);
+
+    let line = single_line(&name, 80);
+
+    assert_eq!(line.chars().count(), 80);
+    assert!(line.ends_with("Chocolate Chocolate…"), "{}", line);
+}
+
+#[test]
+fn parses_numbers_with_either_separator() {
+    assert_eq!(parse_locale_f64("1,5"), Some(1.5));
+    assert_eq!(parse_locale_f64(" 1.5 "), Some(1.5));
+    assert_eq!(parse_locale_f64("12"), Some(12.0));
+    assert_eq!(parse_locale_f64(",5"), Some(0.5));
+    assert_eq!(parse_locale_f64("1.000,5"), None);
+    assert_eq!(parse_locale_f64("1e3"), None);
+    assert_eq!(parse_locale_f64("abc"), None);
+    assert_eq!(parse_locale_f64(","), None);
+    assert_eq!(parse_locale_f64(""), None);
+}
+
+#[test]
+fn reads_the_language_of_posix_locales() {
+    assert_eq!(language_code("fr_FR.UTF-8").as_deref(), Some("fr"));
+    assert_eq!(language_code("de_DE@euro").as_deref(), Some("de"));
+    assert_eq!(language_code("en").as_deref(), Some("en"));
+    assert_eq!(language_code("C.UTF-8"), None);
+    assert_eq!(language_code("POSIX"), None);
+    assert_eq!(language_code(""), None);
+}
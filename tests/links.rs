@@ -0,0 +1,76 @@
+use test_egui::api::{SearchQuery, SortBy};
+use test_egui::links::{parse_product_link, product_link, SearchLink};
+use test_egui::nutrition::{FilterNutrient, RangeFilter};
+
+#[test]
+fn product_links_round_trip() {
+    let link = product_link("3017620422003");
+    assert_eq!(link, "offviewer://product/3017620422003");
+    assert_eq!(parse_product_link(&link), Some("3017620422003"));
+}
+
+#[test]
+fn rejects_foreign_or_malformed_links() {
+    assert_eq!(parse_product_link("https://world.openfoodfacts.org/product/123"), None);
+    assert_eq!(parse_product_link("offviewer://product/"), None);
+    assert_eq!(parse_product_link("offviewer://product/12ab"), None);
+}
+
+#[test]
+fn search_links_round_trip() {
+    let link = SearchLink {
+        query: SearchQuery::Terms("crème & café".to_string()),
+        page: 3,
+        sort: None,
+        filters: Vec::new(),
+    };
+    let text = link.to_link();
+    assert_eq!(text, "offviewer://search?q=cr%C3%A8me%20%26%20caf%C3%A9&page=3");
+    assert_eq!(SearchLink::parse(&text), Some(link));
+
+    let category = SearchLink {
+        query: SearchQuery::Category("en:greek-yogurts".to_string()),
+        page: 1,
+        sort: None,
+        filters: Vec::new(),
+    };
+    assert_eq!(SearchLink::parse(&category.to_link()), Some(category));
+}
+
+#[test]
+fn rejects_malformed_search_links() {
+    assert_eq!(SearchLink::parse("offviewer://search?page=2"), None);
+    assert_eq!(SearchLink::parse("offviewer://search?q=milk&page=0"), None);
+    assert_eq!(SearchLink::parse("offviewer://search?q=%zz"), None);
+    assert_eq!(SearchLink::parse("offviewer://product/123"), None);
+}
+
+#[test]
+fn search_links_carry_the_sort_and_filters() {
+    let link = SearchLink {
+        query: SearchQuery::Terms("yogurt".to_string()),
+        page: 2,
+        sort: Some(SortBy::NutriScore),
+        filters: vec![
+            RangeFilter {
+                max: 10.0,
+                ..RangeFilter::new(FilterNutrient::Sugars)
+            },
+            RangeFilter {
+                min: 0.1,
+                max: 1.5,
+                ..RangeFilter::new(FilterNutrient::Salt)
+            },
+        ],
+    };
+    let text = link.to_link();
+    assert_eq!(text, "offviewer://search?q=yogurt&page=2&sort_by=nutriscore_score&max_sugars=10&min_salt=0.1&max_salt=1.5");
+    assert_eq!(SearchLink::parse(&text), Some(link));
+}
+
+#[test]
+fn rejects_unknown_sorts_and_out_of_range_filters() {
+    assert_eq!(SearchLink::parse("offviewer://search?q=milk&sort_by=price"), None);
+    assert_eq!(SearchLink::parse("offviewer://search?q=milk&max_salt=11"), None);
+    assert_eq!(SearchLink::parse("offviewer://search?q=milk&min_sugars=abc"), None);
+}
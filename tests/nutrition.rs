@@ -0,0 +1,113 @@
+use test_egui::nutrition::parse_serving_size;
+
+#[test]
+fn parses_plain_amount_with_unit() {
+    assert_eq!(parse_serving_size("30 g"), Some((30.0, "g".to_string())));
+    assert_eq!(parse_serving_size("30g"), Some((30.0, "g".to_string())));
+    assert_eq!(parse_serving_size("1,5 L"), Some((1.5, "l".to_string())));
+}
+
+#[test]
+fn prefers_quantity_in_parentheses() {
+    assert_eq!(parse_serving_size("1 tasse (250ml)"), Some((250.0, "ml".to_string())));
+    assert_eq!(parse_serving_size("2 biscuits (25g)"), Some((25.0, "g".to_string())));
+    assert_eq!(parse_serving_size("1 oz (28.35 g)"), Some((28.35, "g".to_string())));
+}
+
+#[test]
+fn falls_back_outside_parentheses() {
+    assert_eq!(parse_serving_size("40 g (about 1 cup)"), Some((40.0, "g".to_string())));
+}
+
+#[test]
+fn rejects_unparseable_sizes() {
+    assert_eq!(parse_serving_size(""), None);
+    assert_eq!(parse_serving_size("1 tasse"), None);
+    assert_eq!(parse_serving_size("une portion"), None);
+}
+
+#[test]
+fn converts_serving_sizes_to_grams() {
+    use test_egui::nutrition::serving_base_amount;
+    assert_eq!(serving_base_amount("1 tasse (25 cl)"), Some(250.0));
+    assert_eq!(serving_base_amount("30 g"), Some(30.0));
+    assert_eq!(serving_base_amount("1 tasse"), None);
+}
+
+#[test]
+fn exports_nutrition_table_as_tsv() {
+    use test_egui::api::Nutriments;
+    use test_egui::nutrition::{nutrient_rows, nutrition_tsv, NutritionBasis};
+
+    let nutriments = Nutriments {
+        fat_100g: Some(30.9),
+        salt_100g: Some(0.107),
+        ..Nutriments::default()
+    };
+    let rows = nutrient_rows(&nutriments);
+
+    let tsv = nutrition_tsv(&rows, Some(15.0), NutritionBasis::PerServing, "en");
+    let lines: Vec<&str> = tsv.lines().collect();
+    assert_eq!(lines[0], "Nutrient\tPer 100g\tPer serving");
+    assert_eq!(lines[1], "Energy (kcal)\t\t");
+    assert_eq!(lines[2], "Fat (g)\t30.9\t4.6");
+    assert_eq!(lines.len(), rows.len() + 1);
+
+    let tsv = nutrition_tsv(&rows, Some(15.0), NutritionBasis::Per100g, "fr");
+    assert!(tsv.starts_with("Nutrient\tPer 100g\n"));
+    assert!(tsv.contains("Salt (g)\t0,1"));
+}
+
+#[test]
+fn computes_percent_of_reference_intakes() {
+    use test_egui::nutrition::percent_daily_value;
+    assert_eq!(percent_daily_value("Energy", 500.0), Some(25.0));
+    assert_eq!(percent_daily_value("Salt", 1.5), Some(25.0));
+    assert_eq!(percent_daily_value("Caffeine", 10.0), None);
+}
+
+#[test]
+fn scales_values_per_100_kcal() {
+    use test_egui::api::Nutriments;
+    use test_egui::nutrition::{nutrient_rows, nutrition_tsv, per_100kcal_factor, NutritionBasis};
+
+    assert_eq!(per_100kcal_factor(Some(400.0)), Some(0.25));
+    assert_eq!(per_100kcal_factor(Some(0.0)), None);
+    assert_eq!(per_100kcal_factor(Some(-5.0)), None);
+    assert_eq!(per_100kcal_factor(None), None);
+
+    let nutriments = Nutriments {
+        energy_kcal_100g: Some(400.0),
+        proteins_100g: Some(20.0),
+        ..Nutriments::default()
+    };
+    let rows = nutrient_rows(&nutriments);
+    let tsv = nutrition_tsv(&rows, None, NutritionBasis::Per100kcal, "en");
+    assert!(tsv.starts_with("Nutrient\tPer 100g\tPer 100 kcal\n"));
+    assert!(tsv.contains("Energy (kcal)\t400.0\t100.0"));
+    assert!(tsv.contains("Proteins (g)\t20.0\t5.0"));
+
+    let water = nutrient_rows(&Nutriments { energy_kcal_100g: Some(0.0), ..Nutriments::default() });
+    assert!(!nutrition_tsv(&water, None, NutritionBasis::Per100kcal, "en").contains("Per 100 kcal"));
+}
+
+#[test]
+fn range_filters_keep_matching_products() {
+    use test_egui::api::Nutriments;
+    use test_egui::nutrition::{FilterNutrient, RangeFilter};
+
+    let sweet = Nutriments { sugars_100g: Some(56.3), ..Nutriments::default() };
+    let plain = Nutriments { sugars_100g: Some(4.0), ..Nutriments::default() };
+    let unknown = Nutriments::default();
+
+    let mut filter = RangeFilter::new(FilterNutrient::Sugars);
+    assert!(!filter.is_active());
+    assert!(filter.matches(&unknown, false));
+
+    filter.max = 5.0;
+    assert!(filter.is_active());
+    assert!(filter.matches(&plain, false));
+    assert!(!filter.matches(&sweet, false));
+    assert!(!filter.matches(&unknown, false));
+    assert!(filter.matches(&unknown, true));
+}
@@ -0,0 +1,86 @@
+use test_egui::api::ProductDetails;
+
+#[test]
+fn accepts_numbers_sent_as_strings() {
+    let product: ProductDetails = serde_json::from_str(
+        r#"{
+            "code": "123",
+            "nutriments": {
+                "energy-kcal_100g": 250,
+                "sugars_100g": "12.5",
+                "salt_100g": "0,3",
+                "fat_100g": null
+            }
+        }"#,
+    )
+    .unwrap();
+    assert_eq!(product.nutriments.energy_kcal_100g, Some(250.0));
+    assert_eq!(product.nutriments.sugars_100g, Some(12.5));
+    assert_eq!(product.nutriments.salt_100g, Some(0.3));
+    assert_eq!(product.nutriments.fat_100g, None);
+}
+
+#[test]
+fn ignores_unusable_values_without_failing_the_product() {
+    let product: ProductDetails = serde_json::from_str(
+        r#"{
+            "code": "123",
+            "product_name": "Messy",
+            "nutriments": {
+                "proteins_100g": "traces",
+                "fiber_100g": [1, 2],
+                "carbohydrates_100g": 40.2
+            }
+        }"#,
+    )
+    .unwrap();
+    assert_eq!(product.product_name.as_deref(), Some("Messy"));
+    assert_eq!(product.nutriments.proteins_100g, None);
+    assert_eq!(product.nutriments.fiber_100g, None);
+    assert_eq!(product.nutriments.carbohydrates_100g, Some(40.2));
+}
+
+#[test]
+fn defaults_missing_nutriments() {
+    let product: ProductDetails = serde_json::from_str(r#"{"code": "123"}"#).unwrap();
+    assert_eq!(product.nutriments.energy_kcal_100g, None);
+}
+
+#[test]
+fn flags_incomplete_ingredient_data() {
+    let parse = |json: &str| serde_json::from_str::<ProductDetails>(json).unwrap();
+
+    let to_be_completed = parse(r#"{"code": "1", "states_tags": ["en:complete", "en:ingredients-to-be-completed"]}"#);
+    assert!(to_be_completed.ingredients_incomplete());
+
+    let unanalysed = parse(r#"{"code": "2", "ingredients_text": "sugar, cocoa", "ingredients_n": "0"}"#);
+    assert!(unanalysed.ingredients_incomplete());
+
+    let complete = parse(r#"{"code": "3", "ingredients_text": "sugar, cocoa", "ingredients_n": 2, "states_tags": ["en:ingredients-completed"]}"#);
+    assert!(!complete.ingredients_incomplete());
+}
+
+#[test]
+fn strips_allergen_markup_from_ingredients() {
+    use test_egui::api::clean_ingredients;
+    assert_eq!(clean_ingredients("Sugar, _milk_ powder, __hazelnuts__ 13%"), "Sugar, milk powder, hazelnuts 13%");
+    assert_eq!(clean_ingredients("_Lait_ écrémé (_soja_),\n  sel"), "Lait écrémé (soja), sel");
+    assert_eq!(clean_ingredients("E_120 colour"), "E_120 colour");
+    assert_eq!(clean_ingredients("   "), "");
+}
+
+#[test]
+fn categories_fall_back_to_their_tags() {
+    let listed: ProductDetails = serde_json::from_str(
+        r#"{"code": "1", "quantity": "400 g", "categories": "Spreads, Sweet spreads", "categories_tags": ["en:spreads"]}"#,
+    )
+    .unwrap();
+    assert_eq!(listed.quantity.as_deref(), Some("400 g"));
+    assert_eq!(listed.categories_label().as_deref(), Some("Spreads, Sweet spreads"));
+
+    let tagged: ProductDetails = serde_json::from_str(r#"{"code": "2", "categories": " ", "categories_tags": ["en:hazelnut-spreads"]}"#).unwrap();
+    assert_eq!(tagged.categories_label().as_deref(), Some("Hazelnut spreads"));
+
+    let bare: ProductDetails = serde_json::from_str(r#"{"code": "3"}"#).unwrap();
+    assert_eq!(bare.categories_label(), None);
+}